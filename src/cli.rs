@@ -1,33 +1,635 @@
-use crate::Tier;
-use clap::Parser;
-use std::path::PathBuf;
-
-#[derive(Debug, Parser)]
-#[command(version, about)]
-pub struct Cli {
-    /// Path to the system definition file
-    #[arg(value_name = "SYSTEM_FILE")]
-    pub system: PathBuf,
-
-    /// Path to the item definition file
-    #[arg(long, default_value = "./examples/items.yaml")]
-    pub items: PathBuf,
-
-    #[arg(short, long)]
-    pub no_factory: bool,
-
-    #[arg(long)]
-    pub max_planets: Option<usize>,
-
-    #[arg(short, long, value_name = "TIER")]
-    pub production_max_tier: Option<Tier>,
-
-    #[arg(short, long, value_name = "TIER")]
-    pub factory_max_tier: Option<Tier>,
-
-    #[arg(long, value_name = "TIER")]
-    pub factory_min_tier: Option<Tier>,
-
-    #[arg(short, long, value_name = "PLANET")]
-    pub include_planet: Vec<String>,
-}
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use pi2::item::ProductOrder;
+use pi2::preset::Preset;
+use pi2::solver::Tiebreak;
+use pi2::Tier;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+/// The environment variable that supplies the items file when `--items` is not given.
+pub const ITEMS_ENV_VAR: &str = "EVE_PI_ITEMS";
+
+/// The items file used when neither `--items` nor [`ITEMS_ENV_VAR`] is given. If it does not
+/// exist, the items bundled with the binary are used instead.
+pub const DEFAULT_ITEMS_PATH: &str = "./examples/items.yaml";
+
+/// The presets file used when `--presets` is not given.
+pub const DEFAULT_PRESETS_PATH: &str = "./presets.yaml";
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Only absent when a subcommand is given.
+    #[command(flatten)]
+    pub args: Option<SolveArgs>,
+
+    /// Only log errors, ignoring RUST_LOG
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log more detail, ignoring RUST_LOG; may be repeated, up to -vvvv for trace logging
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Load a system once and explore it interactively
+    #[cfg(feature = "repl")]
+    Repl(Box<SolveArgs>),
+
+    /// Print every item's recipe and consumers as JSON, without solving a system
+    Graph(ItemsArgs),
+
+    /// List every raw resource ID that may be used in a system file, without solving a system
+    Resources(ItemsArgs),
+
+    /// List every product that can eventually be made from an item, without solving a system
+    Downstream(DownstreamArgs),
+
+    /// List the planets in a system and their resources, without solving it
+    Info(SystemArgs),
+
+    /// Solve every system file in a directory and rank the systems by how much they can produce
+    Scout(ScoutArgs),
+
+    /// Check a system file and items file for every missing or invalid item, rather than stopping
+    /// at the first one
+    Lint(LintArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct SystemArgs {
+    /// Path to the system definition file, or "-" to read it from stdin. Files ending in .json are
+    /// read as a third-party scanner export
+    #[arg(value_name = "SYSTEM_FILE")]
+    pub system: PathBuf,
+
+    /// Path to a scan file of fresh resource densities, keyed by planet label and then resource
+    /// ID, which replace the densities in SYSTEM_FILE
+    #[arg(long, value_name = "SCAN_FILE")]
+    pub scan: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub items: ItemsArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ScoutArgs {
+    /// Path to a directory of system definition files; every file ending in .yaml is solved
+    #[arg(value_name = "DIR")]
+    pub dir: PathBuf,
+
+    /// How to rank the systems
+    #[arg(long, value_enum, default_value_t = ScoutMetric::P4Coverage)]
+    pub sort: ScoutMetric,
+
+    /// The maximum number of planets to use in each system
+    #[arg(short = 'p', long)]
+    pub max_planets: Option<usize>,
+
+    #[command(flatten)]
+    pub items: ItemsArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct DownstreamArgs {
+    /// The ID of the resource or product to start from
+    #[arg(value_name = "ITEM_ID")]
+    pub item: String,
+
+    #[command(flatten)]
+    pub items: ItemsArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct LintArgs {
+    /// Path to the system definition file, or "-" to read it from stdin. Files ending in .json are
+    /// read as a third-party scanner export
+    #[arg(value_name = "SYSTEM_FILE")]
+    pub system: PathBuf,
+
+    #[command(flatten)]
+    pub items: ItemsArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ItemsArgs {
+    /// Path to an item definition file, or "-" to read it from stdin. May be repeated to layer
+    /// several files, which must not define the same item twice. If not given, the path in the
+    /// EVE_PI_ITEMS environment variable is used, followed by ./examples/items.yaml
+    #[arg(long, value_name = "ITEMS_FILE")]
+    pub items: Vec<PathBuf>,
+}
+
+// Clap leaves the group of a struct with flattened fields empty, which would leave `Cli::args`
+// as `None`, so the system file is added to it by hand.
+#[derive(Debug, Args)]
+#[group(args = ["system"])]
+pub struct SolveArgs {
+    #[command(flatten)]
+    pub input: SystemArgs,
+
+    /// Path to another system file whose planets may also be used. May be repeated. Each file's
+    /// jumps_from_factory tells how far its planets are from the factory
+    #[arg(long, value_name = "SYSTEM_FILE")]
+    pub with_system: Vec<PathBuf>,
+
+    /// Instead of printing solutions, solve this second system file and print the products each
+    /// solution gains or loses compared to SYSTEM_FILE. Always printed as text
+    #[arg(long, value_name = "OTHER_SYSTEM_FILE", conflicts_with = "format")]
+    pub diff: Option<PathBuf>,
+
+    /// Keep running, and solve and print the results again whenever the system, items, or scan
+    /// file changes
+    #[cfg(feature = "watch")]
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Fill in any solver options not given on the command line from the preset with this name
+    #[arg(long, value_name = "NAME")]
+    pub preset: Option<String>,
+
+    /// Path to the presets file that --preset is read from
+    #[arg(long, value_name = "PRESETS_FILE", default_value = DEFAULT_PRESETS_PATH, requires = "preset")]
+    pub presets: PathBuf,
+
+    /// Only solve each planet on its own, without a factory planet
+    #[arg(
+        short,
+        long,
+        visible_alias = "planets-only",
+        overrides_with = "factory"
+    )]
+    pub no_factory: bool,
+
+    /// Use a factory planet even if the preset sets no_factory; the default unless --no-factory
+    /// is given
+    #[arg(long, overrides_with = "no_factory")]
+    pub factory: bool,
+
+    #[arg(long)]
+    pub max_planets: Option<usize>,
+
+    /// Solve once for each maximum planet count in this inclusive range, such as "2..6", and
+    /// print the results grouped by count. TOML and YAML print a single document listing each
+    /// count's simulation, and JSON lines print one simulation per line
+    #[arg(long, value_name = "MIN..MAX", value_parser = parse_range, conflicts_with = "max_planets")]
+    pub max_planets_range: Option<RangeInclusive<usize>>,
+
+    #[arg(short, long, value_name = "TIER", ignore_case = true)]
+    pub production_max_tier: Option<Tier>,
+
+    #[arg(short, long, value_name = "TIER", ignore_case = true)]
+    pub factory_max_tier: Option<Tier>,
+
+    /// Shorthand for setting both --production-max-tier and --factory-max-tier; either flag, if
+    /// given, overrides this one
+    #[arg(long, value_name = "TIER", ignore_case = true)]
+    pub max_tier: Option<Tier>,
+
+    /// The highest tier your skills allow you to produce. Caps every other max tier, and products
+    /// above it are reported as blocked by skill level
+    #[arg(long, value_name = "TIER", ignore_case = true)]
+    pub skill_max_tier: Option<Tier>,
+
+    /// Stop solving after this many production cycles, even if new products are still being found
+    #[arg(long, value_name = "N")]
+    pub max_cycles: Option<usize>,
+
+    #[arg(long, value_name = "TIER", ignore_case = true)]
+    pub factory_min_tier: Option<Tier>,
+
+    /// Only keep factory solutions that make at least one product of every tier up to the
+    /// factory's max tier
+    #[arg(long, overrides_with = "no_require_all_tiers")]
+    pub require_all_tiers: bool,
+
+    /// Keep factory solutions that skip a tier even if the preset sets require_all_tiers; the
+    /// default unless --require-all-tiers is given
+    #[arg(long, overrides_with = "require_all_tiers")]
+    pub no_require_all_tiers: bool,
+
+    /// Limit each production planet to supplying this many distinct products to the factory, trying
+    /// every such subset. This reflects limited extractor and facility slots, but can be slow
+    #[arg(long, value_name = "N", conflicts_with = "no_factory")]
+    pub max_products_per_planet: Option<usize>,
+
+    /// Drop solutions that make fewer than this many products at or above the factory's min tier
+    #[arg(long, value_name = "N")]
+    pub min_products: Option<usize>,
+
+    /// Use the planet with this exact label as the factory planet, choosing production planets
+    /// from the rest of the system. The factory planet's own resources, and P1 made from them,
+    /// also feed the factory
+    #[arg(long, value_name = "PLANET", conflicts_with = "no_factory")]
+    pub factory_planet: Option<String>,
+
+    /// Only solve planets whose labels contain this text, ignoring case; may be repeated. A
+    /// trailing Roman numeral must match exactly, so "Tanoo V" does not include "Tanoo VI"
+    #[arg(short, long, value_name = "PLANET")]
+    pub include_planet: Vec<String>,
+
+    /// Only solve planets that extract the resource with this ID; may be repeated to require
+    /// several resources
+    #[arg(long, value_name = "ITEM_ID")]
+    pub planet_has: Vec<String>,
+
+    /// Exclude a planet from solving; may be repeated
+    #[arg(long, value_name = "PLANET")]
+    pub exclude_planet: Vec<String>,
+
+    /// Exclude planets in systems more than this many jumps from the factory
+    #[arg(long, value_name = "N")]
+    pub max_jumps: Option<u32>,
+
+    /// Never produce the item with this ID, or anything made from it; may be repeated
+    #[arg(long, value_name = "ITEM_ID")]
+    pub exclude_item: Vec<String>,
+
+    /// Treat the item with this ID as available to the factory planet without being produced;
+    /// may be repeated
+    #[arg(long, value_name = "ITEM_ID")]
+    pub import: Vec<String>,
+
+    /// Ignore any --import of a P1 item that P4 recipes consume, so the factory only makes P4
+    /// products when production planets supply those inputs, and otherwise stops at P3
+    #[arg(long)]
+    pub no_import_p4_inputs: bool,
+
+    /// Instead of solving normally, find the fewest planets that can produce the item with this
+    /// ID; may be repeated to require several items
+    #[arg(long, value_name = "ITEM_ID")]
+    pub minimize: Vec<String>,
+
+    /// Show each resource's estimated hourly extraction, calculated as this rate multiplied by the
+    /// resource's density
+    #[arg(long, value_name = "U32")]
+    pub base_extraction_rate: Option<u32>,
+
+    /// With --base-extraction-rate, assume every facility completes a production cycle in this
+    /// many hours when estimating how many facilities each product needs; by default P1 facilities
+    /// take 30 minutes and all others an hour
+    #[arg(
+        long,
+        value_name = "F",
+        value_parser = parse_hours,
+        requires = "base_extraction_rate"
+    )]
+    pub cycle_hours: Option<f64>,
+
+    /// Show an estimate of the facilities, CPU, powergrid, and ISK needed to set up each solution
+    #[arg(long)]
+    pub show_cost: bool,
+
+    /// Show how many production stages separate each product from its raw resources
+    #[arg(long)]
+    pub show_depth: bool,
+
+    /// Show each product's yield ratio, the units produced per cycle divided by the total units of
+    /// its inputs
+    #[arg(long)]
+    pub show_yield: bool,
+
+    /// Show input amounts and output quantities in each product's recipe
+    #[arg(long)]
+    pub show_quantities: bool,
+
+    /// List the inputs of each factory product beneath it, one level deep, which shows the lower
+    /// tier items hidden by --factory-min-tier
+    #[arg(long)]
+    pub include_tier_below: bool,
+
+    /// Annotate each factory product with the production planets that supply its inputs
+    #[arg(long)]
+    pub trace_origin: bool,
+
+    /// Include extracted resources alongside each solution's products
+    #[arg(long)]
+    pub show_resources: bool,
+
+    /// How to order the products within each solution; by default factory products are listed
+    /// highest tier first and planet products lowest tier first
+    #[arg(long, value_enum, value_name = "ORDER")]
+    pub sort_products: Option<ProductOrder>,
+
+    /// How to order factory solutions; by default they are printed in the order they were solved
+    #[arg(long, value_enum)]
+    pub sort: Option<SortBy>,
+
+    /// After --sort, rank factory solutions by how many products they make at each tier, highest
+    /// tier first, and break ties with the given comma-separated tiebreaks in priority order, such
+    /// as --tiebreak=planets,labels; without a value, uses density,planets,labels
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "density,planets,labels",
+        value_delimiter = ',',
+        value_name = "TIEBREAKS"
+    )]
+    pub tiebreak: Option<Vec<Tiebreak>>,
+
+    /// Rank factory solutions whose planets share the fewest resources first, ahead of --sort
+    #[arg(long)]
+    pub prefer_diverse: bool,
+
+    /// List every product once under its tier, along with the solutions that produce it, rather
+    /// than listing each solution's products
+    #[arg(long, value_enum)]
+    pub group_by: Option<GroupBy>,
+
+    /// Plan a factory purely for P4 products. Production planets make up to P3 unless told
+    /// otherwise, any P3 item they don't make is imported, and only P4 products are shown
+    #[arg(
+        long,
+        conflicts_with_all = ["no_factory", "factory_max_tier", "max_tier", "factory_min_tier"]
+    )]
+    pub p4_factory: bool,
+
+    /// Solve each planet on its own and only print the one that reaches the highest tier, breaking
+    /// ties by product count and then resource density
+    #[arg(
+        long,
+        conflicts_with_all = ["factory_planet", "diff", "minimize", "max_planets_range"]
+    )]
+    pub best_planet: bool,
+
+    /// Print at most this many solutions
+    #[arg(long, value_name = "N")]
+    pub top: Option<usize>,
+
+    /// List the products more than one production planet makes in each factory solution, along
+    /// with a suggested planet for each so that every product is only made once
+    #[arg(long, conflicts_with = "no_factory")]
+    pub unique_production: bool,
+
+    /// Warn about production planets in a factory solution that only make products other planets
+    /// in it already supply, so they could be used elsewhere
+    #[arg(long, conflicts_with = "no_factory")]
+    pub warn_redundant: bool,
+
+    /// Warn about factory solutions whose intermediate products are consumed faster than one
+    /// facility each can make them, along with how many times the supply each one needs
+    #[arg(long, conflicts_with = "no_factory")]
+    pub warn_shortfalls: bool,
+
+    /// Flag products made from a resource extracted at a density below this, from 0 to 1, since
+    /// those products may not be reliable once yields fluctuate
+    #[arg(long, value_name = "DENSITY")]
+    pub warn_marginal: Option<f32>,
+
+    /// After the solutions, list every product that no solution was able to make
+    #[arg(long)]
+    pub report_unreachable: bool,
+
+    /// After the solutions, show how many of the catalog's P4 items any solution can make
+    #[arg(long)]
+    pub report_coverage: bool,
+
+    /// After the solutions, explain each tier no solution reaches by listing its recipes and the
+    /// inputs they are missing
+    #[arg(long)]
+    pub diagnose: bool,
+
+    /// After the solutions, list how many factory solutions each production planet appears in
+    #[arg(long)]
+    pub report_frequency: bool,
+
+    /// After the solutions, list the planets whose products aren't used as inputs by any factory
+    /// solution, which are candidates to skip when scanning
+    #[arg(long, conflicts_with = "no_factory")]
+    pub report_non_contributing: bool,
+
+    /// After the solutions, list the products of each solution shown that no single planet could
+    /// make on its own, which shows what the factory planet adds
+    #[arg(long, conflicts_with = "no_factory")]
+    pub report_factory_delta: bool,
+
+    /// After the solutions, list the products each production cycle added for every solution
+    /// shown, to help explain why a chain does or doesn't complete
+    #[arg(long)]
+    pub show_cycles: bool,
+
+    /// When to color products by tier; requires the `color` feature
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// The format to print the simulation in
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// A free-form note, such as what the plan is for, included as a top-level `note` field in
+    /// structured output and as a header in text output
+    #[arg(long, value_name = "STRING")]
+    pub note: Option<String>,
+}
+
+impl SolveArgs {
+    /// The lowest tier of factory products to show, which is P4 with `--p4-factory`.
+    pub fn factory_min_tier(&self) -> Tier {
+        if self.p4_factory {
+            Tier::P4
+        } else {
+            self.factory_min_tier.unwrap_or(Tier::R0)
+        }
+    }
+
+    /// Fills in any solver options that were not given on the command line from `preset`. Flags
+    /// the preset turns on may be turned back off with `--factory` and `--no-require-all-tiers`.
+    pub fn apply_preset(&mut self, preset: Preset) {
+        if !self.factory {
+            self.no_factory |= preset.no_factory.unwrap_or(false);
+        }
+
+        self.max_planets = self.max_planets.or(preset.max_planets);
+        self.production_max_tier = self.production_max_tier.or(preset.production_max_tier);
+        self.factory_max_tier = self.factory_max_tier.or(preset.factory_max_tier);
+        self.max_tier = self.max_tier.or(preset.max_tier);
+        self.factory_min_tier = self.factory_min_tier.or(preset.factory_min_tier);
+        self.max_cycles = self.max_cycles.or(preset.max_cycles);
+
+        if !self.no_require_all_tiers {
+            self.require_all_tiers |= preset.require_all_tiers.unwrap_or(false);
+        }
+
+        self.min_products = self.min_products.or(preset.min_products);
+
+        if self.include_planet.is_empty() {
+            self.include_planet = preset.include_planets;
+        }
+
+        if self.exclude_planet.is_empty() {
+            self.exclude_planet = preset.exclude_planets;
+        }
+
+        if self.exclude_item.is_empty() {
+            self.exclude_item = preset.exclude_items;
+        }
+
+        if self.import.is_empty() {
+            self.import = preset.imports;
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Human-readable text
+    Text,
+
+    /// The full simulation as TOML; see `Simulation` for the schema
+    Toml,
+
+    /// The full simulation as YAML, with the same structure as TOML
+    Yaml,
+
+    /// Products in aligned columns; requires the `table` feature
+    #[cfg(feature = "table")]
+    Table,
+
+    /// One JSON object per line, printed as each solution is solved
+    Jsonl,
+
+    /// One line per solution listing only its product IDs, separated by commas
+    Ids,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color output when writing to a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum SortBy {
+    /// Alphabetically by the production planets' labels
+    Planets,
+
+    /// Highest estimated ISK value per hour first
+    Value,
+
+    /// Highest tier product first
+    Tier,
+
+    /// Highest combined resource density across the production planets first
+    Density,
+
+    /// Fewest total jumps from the factory to the production planets first
+    Jumps,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum ScoutMetric {
+    /// Largest share of P4 products that can be made first
+    P4Coverage,
+
+    /// Highest tier product first
+    MaxTier,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum GroupBy {
+    /// One section per tier, lowest first
+    Tier,
+}
+
+fn parse_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = value
+        .split_once("..")
+        .ok_or_else(|| format!("expected a range such as 2..6, got '{value}'"))?;
+
+    let start: usize = start.parse().map_err(|e| format!("invalid start: {e}"))?;
+    let end: usize = end.parse().map_err(|e| format!("invalid end: {e}"))?;
+
+    if start == 0 || start > end {
+        return Err(format!("expected 0 < MIN <= MAX, got '{value}'"));
+    }
+
+    Ok(start..=end)
+}
+
+fn parse_hours(value: &str) -> Result<f64, String> {
+    let hours: f64 = value.parse().map_err(|e| format!("invalid number: {e}"))?;
+
+    if !hours.is_finite() || hours <= 0.0 {
+        return Err(format!("expected a number of hours above 0, got '{value}'"));
+    }
+
+    Ok(hours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(args: &[&str], preset: Preset) -> SolveArgs {
+        let mut args = Cli::try_parse_from(["pi2", "system.yaml"].iter().chain(args))
+            .unwrap()
+            .args
+            .unwrap();
+
+        args.apply_preset(preset);
+        args
+    }
+
+    fn preset() -> Preset {
+        Preset {
+            no_factory: Some(true),
+            max_planets: Some(3),
+            require_all_tiers: Some(true),
+            exclude_planets: vec!["Tanoo I".to_owned()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn preset_fills_in_missing_options() {
+        let args = apply(&[], preset());
+
+        assert!(args.no_factory);
+        assert_eq!(args.max_planets, Some(3));
+        assert!(args.require_all_tiers);
+        assert_eq!(args.exclude_planet, ["Tanoo I"]);
+    }
+
+    #[test]
+    fn command_line_wins_over_preset() {
+        let args = apply(
+            &[
+                "--max-planets",
+                "5",
+                "--exclude-planet",
+                "Tanoo II",
+                "--factory",
+                "--no-require-all-tiers",
+            ],
+            preset(),
+        );
+
+        assert!(!args.no_factory);
+        assert_eq!(args.max_planets, Some(5));
+        assert!(!args.require_all_tiers);
+        assert_eq!(args.exclude_planet, ["Tanoo II"]);
+    }
+
+    #[test]
+    fn last_of_negated_flags_wins() {
+        let args = apply(&["--factory", "--no-factory"], Preset::default());
+        assert!(args.no_factory);
+
+        let args = apply(&["--no-factory", "--factory"], preset());
+        assert!(!args.no_factory);
+    }
+}
@@ -0,0 +1,64 @@
+use crate::cli::ColorChoice;
+use pi2::Tier;
+use std::env;
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+
+/// Colors text by tier when color output is enabled.
+#[derive(Debug, Copy, Clone)]
+pub struct Painter {
+    enabled: bool,
+}
+
+impl Painter {
+    /// Resolves `choice` against the environment. When set to [`ColorChoice::Auto`], color is only
+    /// used if stdout is a terminal and the `NO_COLOR` environment variable is unset or empty.
+    pub fn new(choice: ColorChoice) -> Self {
+        let enabled = cfg!(feature = "color")
+            && match choice {
+                ColorChoice::Always => true,
+                ColorChoice::Never => false,
+                ColorChoice::Auto => {
+                    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                    !no_color && io::stdout().is_terminal()
+                }
+            };
+
+        Self { enabled }
+    }
+
+    pub fn paint<D>(&self, tier: Tier, value: D) -> String
+    where
+        D: Display,
+    {
+        if !self.enabled {
+            return value.to_string();
+        }
+
+        paint(tier, value)
+    }
+}
+
+#[cfg(feature = "color")]
+fn paint<D>(tier: Tier, value: D) -> String
+where
+    D: Display,
+{
+    use owo_colors::OwoColorize;
+
+    match tier {
+        Tier::R0 => value.bright_black().to_string(),
+        Tier::P1 => value.green().to_string(),
+        Tier::P2 => value.cyan().to_string(),
+        Tier::P3 => value.yellow().to_string(),
+        Tier::P4 => value.magenta().to_string(),
+    }
+}
+
+#[cfg(not(feature = "color"))]
+fn paint<D>(_tier: Tier, value: D) -> String
+where
+    D: Display,
+{
+    value.to_string()
+}
@@ -1,24 +1,69 @@
-use std::io;
-
-pub type Result<T> = std::result::Result<T, Error>;
-
-#[derive(Debug, thiserror::Error)]
-pub enum Error {
-    #[error("missing item with ID '{0}'")]
-    MissingItem(String),
-
-    #[error("io error: {0}")]
-    IO(#[from] io::Error),
-
-    #[error("deserialize error: {0}")]
-    Deserialize(#[from] serde_yaml::Error),
-}
-
-impl Error {
-    pub fn create_missing_item<Id>(item_id: Id) -> Self
-    where
-        Id: ToString,
-    {
-        Self::MissingItem(item_id.to_string())
-    }
-}
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("missing item with ID '{0}'")]
+    MissingItem(String),
+
+    #[error("system '{0}' does not have any planets")]
+    EmptySystem(String),
+
+    #[error("items file '{0}' does not define any items")]
+    EmptyItems(String),
+
+    #[error("item with ID '{0}' is defined more than once")]
+    DuplicateItem(String),
+
+    #[error("item with ID '{0}' has a production without any inputs")]
+    EmptyProduction(String),
+
+    #[error("item with ID '{product}' requires missing input '{input}'")]
+    MissingInput { product: String, input: String },
+
+    #[error("item with ID '{0}' requires itself somewhere in its production chain")]
+    ProductionCycle(String),
+
+    #[error("planet '{planet}' lists '{item}' as a resource, but it is not an R0 item")]
+    NonResourceItem { planet: String, item: String },
+
+    #[error("system does not have a planet labelled '{0}'")]
+    MissingPlanet(String),
+
+    #[error("saved simulation was solved from different inputs or options")]
+    StaleSimulation,
+
+    #[error("presets file does not define a preset named '{0}'")]
+    MissingPreset(String),
+
+    #[error("planet '{planet}' has an invalid command center level of {level}")]
+    InvalidCommandCenterLevel { planet: String, level: u8 },
+
+    #[error("io error: {0}")]
+    IO(#[from] io::Error),
+
+    #[error("deserialize error: {0}")]
+    Deserialize(#[from] serde_yaml::Error),
+
+    #[error("json deserialize error: {0}")]
+    JsonDeserialize(serde_json::Error),
+
+    #[error("toml serialize error: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+
+    #[error("yaml serialize error: {0}")]
+    YamlSerialize(serde_yaml::Error),
+
+    #[error("json serialize error: {0}")]
+    JsonSerialize(#[from] serde_json::Error),
+}
+
+impl Error {
+    pub fn create_missing_item<Id>(item_id: Id) -> Self
+    where
+        Id: ToString,
+    {
+        Self::MissingItem(item_id.to_string())
+    }
+}
@@ -0,0 +1,149 @@
+use crate::Tier;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+/// The highest level a command center can be upgraded to.
+pub const MAX_COMMAND_CENTER_LEVEL: u8 = 5;
+
+/// CPU (in tf) and powergrid (in MW) provided by a command center, indexed by upgrade level.
+const COMMAND_CENTER_BUDGETS: [Budget; MAX_COMMAND_CENTER_LEVEL as usize + 1] = [
+    Budget::new(1675, 6000),
+    Budget::new(7057, 9000),
+    Budget::new(12136, 12000),
+    Budget::new(17215, 15000),
+    Budget::new(21315, 17000),
+    Budget::new(25415, 19000),
+];
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct Budget {
+    pub cpu: u32,
+    pub powergrid: u32,
+}
+
+impl Budget {
+    pub const fn new(cpu: u32, powergrid: u32) -> Self {
+        Self { cpu, powergrid }
+    }
+
+    /// Returns the CPU and powergrid available to a command center of the given level, or `None`
+    /// if the level is higher than [`MAX_COMMAND_CENTER_LEVEL`].
+    pub fn for_command_center(level: u8) -> Option<Self> {
+        COMMAND_CENTER_BUDGETS.get(usize::from(level)).copied()
+    }
+
+    /// Returns `true` if `usage` needs no more CPU or powergrid than this budget provides.
+    pub fn covers(&self, usage: Budget) -> bool {
+        usage.cpu <= self.cpu && usage.powergrid <= self.powergrid
+    }
+}
+
+impl std::ops::Add for Budget {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.cpu + rhs.cpu, self.powergrid + rhs.powergrid)
+    }
+}
+
+impl std::iter::Sum for Budget {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |total, budget| total + budget)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Facility {
+    /// An extractor control unit, without any extractor heads.
+    Extractor,
+    BasicIndustry,
+    AdvancedIndustry,
+    HighTechProduction,
+}
+
+impl Facility {
+    /// Returns the facility used to produce items of the given tier, or `None` for R0 resources,
+    /// which are extracted rather than produced.
+    pub fn for_tier(tier: Tier) -> Option<Self> {
+        match tier {
+            Tier::R0 => None,
+            Tier::P1 => Some(Self::BasicIndustry),
+            Tier::P2 | Tier::P3 => Some(Self::AdvancedIndustry),
+            Tier::P4 => Some(Self::HighTechProduction),
+        }
+    }
+
+    /// Returns the CPU and powergrid the facility uses once placed.
+    pub fn usage(&self) -> Budget {
+        match self {
+            Self::Extractor => Budget::new(400, 2600),
+            Self::BasicIndustry => Budget::new(200, 800),
+            Self::AdvancedIndustry => Budget::new(500, 700),
+            Self::HighTechProduction => Budget::new(400, 1100),
+        }
+    }
+
+    /// Returns the approximate ISK it costs to buy the facility. Actual market prices vary.
+    pub fn isk_cost(&self) -> u64 {
+        match self {
+            Self::Extractor => 45_000,
+            Self::BasicIndustry => 75_000,
+            Self::AdvancedIndustry => 250_000,
+            Self::HighTechProduction => 525_000,
+        }
+    }
+}
+
+/// The total infrastructure needed to place a set of facilities.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct FacilityCost {
+    pub facilities: u32,
+    pub usage: Budget,
+    pub isk: u64,
+}
+
+impl FacilityCost {
+    /// Sums the cost of placing every facility in `facilities`.
+    pub fn of<I>(facilities: I) -> Self
+    where
+        I: IntoIterator<Item = Facility>,
+    {
+        facilities
+            .into_iter()
+            .map(|facility| Self {
+                facilities: 1,
+                usage: facility.usage(),
+                isk: facility.isk_cost(),
+            })
+            .sum()
+    }
+}
+
+impl Display for FacilityCost {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} facilities, {} tf CPU, {} MW powergrid, ~{} ISK",
+            self.facilities, self.usage.cpu, self.usage.powergrid, self.isk
+        )
+    }
+}
+
+impl std::ops::Add for FacilityCost {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            facilities: self.facilities + rhs.facilities,
+            usage: self.usage + rhs.usage,
+            isk: self.isk + rhs.isk,
+        }
+    }
+}
+
+impl std::iter::Sum for FacilityCost {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |total, cost| total + cost)
+    }
+}
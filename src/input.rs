@@ -0,0 +1,46 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The path that, when given in place of a file, causes input to be read from stdin instead.
+pub const STDIN_PATH: &str = "-";
+
+/// Returns `true` if `path` refers to stdin rather than a file.
+pub fn is_stdin<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    path.as_ref() == Path::new(STDIN_PATH)
+}
+
+/// Reads the entire contents of `path` into a string, reading from stdin if the path is
+/// [`STDIN_PATH`]. With the `gzip` feature enabled, files ending in `.gz` are decompressed.
+pub fn read_to_string<P>(path: P) -> io::Result<String>
+where
+    P: AsRef<Path>,
+{
+    if is_stdin(&path) {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+
+        return Ok(contents);
+    }
+
+    #[cfg(feature = "gzip")]
+    if path.as_ref().extension().is_some_and(|ext| ext == "gz") {
+        return read_gzip_to_string(path);
+    }
+
+    fs::read_to_string(path)
+}
+
+#[cfg(feature = "gzip")]
+fn read_gzip_to_string<P>(path: P) -> io::Result<String>
+where
+    P: AsRef<Path>,
+{
+    let mut contents = String::new();
+    flate2::read::GzDecoder::new(fs::File::open(path)?).read_to_string(&mut contents)?;
+
+    Ok(contents)
+}
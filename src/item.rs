@@ -1,202 +1,854 @@
-use crate::error::Error;
-use crate::{error, Tier};
-use log::{debug, trace, warn};
-use serde::Deserialize;
-use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
-use std::fmt::{Display, Formatter};
-use std::fs;
-use std::hash::{Hash, Hasher};
-use std::path::Path;
-
-#[derive(Debug, Clone)]
-pub struct Item<'a> {
-    pub id: &'a str,
-    pub label: &'a str,
-    pub tier: Tier,
-    pub is_p4_input: bool,
-    pub production: Option<Production<'a>>,
-}
-
-impl Hash for Item<'_> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.id.hash(state)
-    }
-}
-
-impl PartialEq for Item<'_> {
-    fn eq(&self, other: &Self) -> bool {
-        self.id.eq(other.id)
-    }
-}
-
-impl Eq for Item<'_> {}
-
-impl PartialOrd for Item<'_> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl Ord for Item<'_> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.id.cmp(other.id)
-    }
-}
-
-impl<'a> Item<'a> {
-    fn from_raw(item_manager: &'a ItemManager, raw_item: &'a RawItem) -> error::Result<Self> {
-        Ok(Self {
-            id: &raw_item.id,
-            label: &raw_item.label,
-            tier: raw_item.tier,
-            is_p4_input: raw_item.is_p4_input,
-            production: raw_item
-                .production
-                .as_ref()
-                .map(|raw| Production::from_raw(item_manager, raw))
-                .transpose()?,
-        })
-    }
-}
-
-impl Display for Item<'_> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if let Some(production) = &self.production {
-            for (index, input) in production.inputs.iter().enumerate() {
-                if index > 0 {
-                    f.write_str(" + ")?;
-                }
-
-                f.write_str(input.item.label)?;
-            }
-
-            f.write_str(" → ")?;
-        }
-
-        f.write_str(self.label)
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct Production<'a> {
-    pub quantity: u16,
-    pub inputs: Vec<Input<'a>>,
-}
-
-impl<'a> Production<'a> {
-    fn from_raw(
-        item_manager: &'a ItemManager,
-        raw_production: &'a RawProduction,
-    ) -> error::Result<Self> {
-        let mut inputs: Vec<Input<'a>> = Vec::new();
-
-        for (item_id, amount) in &raw_production.inputs {
-            let Some(item) = item_manager.get(item_id) else {
-                return Err(Error::MissingItem(String::from(item_id)));
-            };
-
-            inputs.push(Input {
-                item,
-                amount: *amount,
-            })
-        }
-
-        Ok(Self {
-            quantity: raw_production.quantity,
-            inputs,
-        })
-    }
-
-    pub fn can_be_made_using(&self, possible_inputs: &HashSet<&Item<'a>>) -> bool {
-        self.inputs
-            .iter()
-            .all(|input| possible_inputs.contains(&input.item))
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct Input<'a> {
-    pub item: Item<'a>,
-    pub amount: u16,
-}
-
-type ItemMap = HashMap<String, RawItem>;
-type UsedInMap = HashMap<String, HashSet<String>>;
-
-#[derive(Debug)]
-pub struct ItemManager {
-    items: ItemMap,
-    used_in: UsedInMap,
-}
-
-impl ItemManager {
-    pub fn new<P>(items_file: P) -> error::Result<Self>
-    where
-        P: AsRef<Path>,
-    {
-        let mut items: ItemMap = serde_yaml::from_str(&fs::read_to_string(items_file)?)?;
-        let mut used_in = UsedInMap::new();
-
-        for (id, item) in items.iter_mut() {
-            item.id = id.clone();
-
-            if let Some(production) = &item.production {
-                for input in production.inputs.keys() {
-                    used_in
-                        .entry(input.to_string())
-                        .or_default()
-                        .insert(id.to_string());
-                }
-            }
-
-            trace!("Finished initializing {id}");
-        }
-
-        debug!("Item manager initialized with {} item(s)", items.len());
-
-        debug!(
-            "Item manager initialized with {} mapped product(s)",
-            used_in.len()
-        );
-
-        Ok(Self { items, used_in })
-    }
-
-    pub fn get<Id>(&self, item_id: Id) -> Option<Item>
-    where
-        Id: AsRef<str>,
-    {
-        let Some(raw_item) = self.items.get(item_id.as_ref()) else {
-            warn!("Could not find item with ID '{}'", item_id.as_ref());
-            return None;
-        };
-
-        Item::from_raw(self, raw_item).ok()
-    }
-
-    pub fn get_products<'a>(&self, item: &'a Item<'a>) -> Option<Vec<Item>> {
-        let Some(products) = self.used_in.get(item.id) else {
-            return None;
-        };
-
-        Some(products.iter().map(|id| self.get(id).unwrap()).collect())
-    }
-}
-
-#[derive(Debug, Deserialize)]
-struct RawItem {
-    #[serde(default)]
-    id: String,
-    label: String,
-    tier: Tier,
-    production: Option<RawProduction>,
-    #[serde(default)]
-    is_p4_input: bool,
-}
-
-#[derive(Debug, Deserialize)]
-struct RawProduction {
-    quantity: u16,
-    inputs: HashMap<String, u16>,
-}
+use crate::error::Error;
+use crate::{error, input, Tier};
+use clap::ValueEnum;
+use itertools::Itertools;
+use log::{debug, trace, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Item<'a> {
+    pub id: &'a str,
+    pub label: &'a str,
+    pub tier: Tier,
+    /// The estimated market value of a single unit, in ISK.
+    #[serde(skip)]
+    pub value: f64,
+    #[serde(skip)]
+    pub is_p4_input: bool,
+    #[serde(skip)]
+    pub production: Option<Production<'a>>,
+}
+
+impl Hash for Item<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state)
+    }
+}
+
+impl PartialEq for Item<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id.eq(other.id)
+    }
+}
+
+impl Eq for Item<'_> {}
+
+impl PartialOrd for Item<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Item<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(other.id)
+    }
+}
+
+impl<'a> Item<'a> {
+    fn from_raw(item_manager: &'a ItemManager, raw_item: &'a RawItem) -> error::Result<Self> {
+        Ok(Self {
+            id: &raw_item.id,
+            label: &raw_item.label,
+            tier: raw_item.tier,
+            value: raw_item.value,
+            is_p4_input: raw_item.is_p4_input,
+            production: raw_item
+                .production
+                .as_ref()
+                .map(|raw| Production::from_raw(item_manager, &raw_item.id, raw))
+                .transpose()?,
+        })
+    }
+
+    /// Returns the resources at the root of this item's production chain. An item with no
+    /// production information is considered to be its own root resource.
+    pub fn root_resources(&self) -> BTreeSet<&Item<'a>> {
+        let mut resources = BTreeSet::new();
+        self.collect_root_resources(&mut resources);
+
+        resources
+    }
+
+    /// Returns the number of production stages between this item and its root resources, following
+    /// the longest path. An item can only be loaded if its chain has no cycles, so unlike
+    /// [`ItemManager::chain_depth()`] this cannot fail.
+    pub fn chain_depth(&self) -> usize {
+        self.production.as_ref().map_or(0, |production| {
+            production
+                .inputs
+                .iter()
+                .map(|input| input.item.chain_depth())
+                .max()
+                .unwrap_or(0)
+                + 1
+        })
+    }
+
+    /// Returns a [`Display`] implementation for the item that, if `show_quantities` is `true`,
+    /// renders its recipe as "2×A + 3×B → 40×C" rather than "A + B → C".
+    pub fn with_quantities(&self, show_quantities: bool) -> ItemDisplay<'_, 'a> {
+        ItemDisplay {
+            item: self,
+            show_quantities,
+        }
+    }
+
+    /// Returns `true` if `other` appears anywhere in this item's production chain.
+    pub fn requires(&self, other: &Item) -> bool {
+        self.production.as_ref().is_some_and(|production| {
+            production
+                .inputs
+                .iter()
+                .any(|input| input.item == *other || input.item.requires(other))
+        })
+    }
+
+    fn collect_root_resources<'s>(&'s self, resources: &mut BTreeSet<&'s Item<'a>>) {
+        match &self.production {
+            Some(production) => {
+                for input in &production.inputs {
+                    input.item.collect_root_resources(resources);
+                }
+            }
+            None => {
+                resources.insert(self);
+            }
+        }
+    }
+}
+
+impl Display for Item<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.with_quantities(false).fmt(f)
+    }
+}
+
+/// Displays an item's recipe, optionally including the amount of each input and the quantity
+/// produced. See [`Item::with_quantities()`].
+pub struct ItemDisplay<'i, 'a> {
+    item: &'i Item<'a>,
+    show_quantities: bool,
+}
+
+impl Display for ItemDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Some(production) = &self.item.production else {
+            return f.write_str(self.item.label);
+        };
+
+        for (index, input) in production.inputs.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" + ")?;
+            }
+
+            if self.show_quantities {
+                write!(f, "{}×", input.amount)?;
+            }
+
+            f.write_str(input.item.label)?;
+        }
+
+        f.write_str(" → ")?;
+
+        if self.show_quantities {
+            write!(f, "{}×", production.quantity)?;
+        }
+
+        f.write_str(self.item.label)
+    }
+}
+
+/// An order in which to list items.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum ProductOrder {
+    /// Lowest tier first
+    Tier,
+
+    /// Alphabetically by label
+    Label,
+
+    /// Fewest recipe inputs first, so raw resources come before everything else
+    Complexity,
+}
+
+impl ProductOrder {
+    /// Compares two items in this order, falling back to tier and then label to break ties.
+    pub fn compare(self, a: &Item, b: &Item) -> Ordering {
+        let input_count = |item: &Item| item.production.as_ref().map_or(0, |p| p.inputs.len());
+        let by_tier = |a: &Item, b: &Item| a.tier.cmp(&b.tier).then_with(|| a.label.cmp(b.label));
+
+        match self {
+            Self::Tier => by_tier(a, b),
+            Self::Label => a.label.cmp(b.label),
+            Self::Complexity => input_count(a)
+                .cmp(&input_count(b))
+                .then_with(|| by_tier(a, b)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Production<'a> {
+    pub quantity: u16,
+    pub inputs: Vec<Input<'a>>,
+}
+
+impl<'a> Production<'a> {
+    fn from_raw(
+        item_manager: &'a ItemManager,
+        product_id: &str,
+        raw_production: &'a RawProduction,
+    ) -> error::Result<Self> {
+        let mut inputs: Vec<Input<'a>> = Vec::new();
+
+        for (item_id, amount) in &raw_production.inputs {
+            let Some(item) = item_manager.get(item_id) else {
+                return Err(Error::MissingInput {
+                    product: product_id.to_string(),
+                    input: item_id.clone(),
+                });
+            };
+
+            inputs.push(Input {
+                item,
+                amount: *amount,
+            })
+        }
+
+        Ok(Self {
+            quantity: raw_production.quantity,
+            inputs,
+        })
+    }
+
+    /// Returns `true` if every input of the production is in `possible_inputs`. Productions
+    /// always have at least one input, as items with empty productions are rejected when loaded.
+    pub fn can_be_made_using(&self, possible_inputs: &BTreeSet<&Item<'a>>) -> bool {
+        self.inputs
+            .iter()
+            .all(|input| possible_inputs.contains(&input.item))
+    }
+
+    /// Returns the inputs of the production that are not in `available_inputs`, in recipe order.
+    pub fn missing_inputs(&self, available_inputs: &BTreeSet<&Item<'a>>) -> Vec<&Item<'a>> {
+        self.inputs
+            .iter()
+            .map(|input| &input.item)
+            .filter(|item| !available_inputs.contains(item))
+            .collect()
+    }
+
+    /// Returns the units produced per cycle divided by the total units of every input consumed,
+    /// which shows how much a recipe compresses its inputs.
+    pub fn yield_ratio(&self) -> f32 {
+        let inputs: u32 = self
+            .inputs
+            .iter()
+            .map(|input| u32::from(input.amount))
+            .sum();
+
+        f32::from(self.quantity) / inputs as f32
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Input<'a> {
+    pub item: Item<'a>,
+    pub amount: u16,
+}
+
+/// Combines inputs of the same item into one, summing their amounts, which is useful when
+/// totalling the inputs of several productions. Amounts saturate at [`u16::MAX`]. The merged
+/// inputs are sorted by item ID.
+pub fn merge_inputs(inputs: Vec<Input<'_>>) -> Vec<Input<'_>> {
+    let mut merged: BTreeMap<Item, u16> = BTreeMap::new();
+
+    for input in inputs {
+        let amount = merged.entry(input.item).or_default();
+        *amount = amount.saturating_add(input.amount);
+    }
+
+    merged
+        .into_iter()
+        .map(|(item, amount)| Input { item, amount })
+        .collect()
+}
+
+/// A single item in [`ItemManager::graph()`].
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphNode<'a> {
+    pub id: &'a str,
+    pub label: &'a str,
+    pub tier: Tier,
+    /// The number of units made per production cycle, if the item can be produced.
+    pub quantity: Option<u16>,
+    /// The ID of each input, mapped to the amount consumed per production cycle.
+    pub inputs: BTreeMap<&'a str, u16>,
+    /// The IDs of the items that consume this one.
+    pub used_in: BTreeSet<&'a str>,
+}
+
+type ItemMap = BTreeMap<String, RawItem>;
+type AliasMap = BTreeMap<String, String>;
+type UsedInMap = BTreeMap<String, BTreeSet<String>>;
+
+#[derive(Debug)]
+pub struct ItemManager {
+    items: ItemMap,
+    aliases: AliasMap,
+    used_in: UsedInMap,
+}
+
+impl ItemManager {
+    /// Loads and merges the items defined in each of `items_files`, along with any files they
+    /// include. An item ID may only be defined once across all of the files.
+    pub fn new<I, P>(items_files: I) -> error::Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let (items, source) = load_items_files(items_files)?;
+        Self::from_items(items, &source)
+    }
+
+    /// Loads items files in the same way as [`ItemManager::new()`], but rather than failing on
+    /// the first invalid item, returns every problem found alongside an item manager built from
+    /// the files as-is. Items with missing inputs or production cycles cannot be retrieved from
+    /// that manager. Files that cannot be read or parsed still fail immediately.
+    pub fn lint<I, P>(items_files: I) -> error::Result<(Self, Vec<Error>)>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let (items, source) = load_items_files(items_files)?;
+        Ok(Self::from_items_unchecked(items, &source))
+    }
+
+    fn from_items(items: ItemMap, source: &str) -> error::Result<Self> {
+        let (item_manager, problems) = Self::from_items_unchecked(items, source);
+
+        match problems.into_iter().next() {
+            Some(problem) => Err(problem),
+            None => Ok(item_manager),
+        }
+    }
+
+    /// Builds an item manager from `items`, skipping over any invalid aliases or inputs rather
+    /// than failing. Returns the manager along with a problem for each one skipped.
+    fn from_items_unchecked(mut items: ItemMap, source: &str) -> (Self, Vec<Error>) {
+        let mut problems = Vec::new();
+
+        if items.is_empty() {
+            problems.push(Error::EmptyItems(source.to_string()));
+        }
+
+        let mut aliases = AliasMap::new();
+
+        for (id, item) in &items {
+            for alias in &item.aliases {
+                if items.contains_key(alias) || aliases.contains_key(alias) {
+                    problems.push(Error::DuplicateItem(alias.clone()));
+                    continue;
+                }
+
+                aliases.insert(alias.clone(), id.clone());
+            }
+        }
+
+        let ids: HashSet<String> = items.keys().cloned().collect();
+        let mut used_in = UsedInMap::new();
+
+        for (id, item) in items.iter_mut() {
+            item.id = id.clone();
+
+            if let Some(production) = &item.production {
+                // A recipe without inputs could be "made" from nothing, so items that need no
+                // inputs should instead be defined without a production.
+                if production.inputs.is_empty() {
+                    problems.push(Error::EmptyProduction(id.clone()));
+                }
+
+                for input in production.inputs.keys() {
+                    let input = aliases.get(input).unwrap_or(input);
+
+                    if !ids.contains(input) {
+                        problems.push(Error::MissingInput {
+                            product: id.clone(),
+                            input: input.clone(),
+                        });
+
+                        continue;
+                    }
+
+                    used_in
+                        .entry(input.to_string())
+                        .or_default()
+                        .insert(id.to_string());
+                }
+            }
+
+            trace!("Finished initializing {id}");
+        }
+
+        debug!("Item manager initialized with {} item(s)", items.len());
+
+        debug!(
+            "Item manager initialized with {} mapped product(s)",
+            used_in.len()
+        );
+
+        let mut item_manager = Self {
+            items,
+            aliases,
+            used_in,
+        };
+
+        // Loading an item whose chain requires itself would never finish, so cycles are reported
+        // up front, and those items are left out of the manager. Missing inputs were already
+        // reported above.
+        let mut cyclic = HashSet::new();
+
+        for id in item_manager.items.keys() {
+            if let Err(problem @ Error::ProductionCycle(_)) = item_manager.chain_depth(id) {
+                problems.push(problem);
+                cyclic.insert(id.clone());
+            }
+        }
+
+        item_manager.items.retain(|id, _| !cyclic.contains(id));
+
+        (item_manager, problems)
+    }
+
+    /// Feeds every item definition, as loaded, into `hasher`. See [`Solver::input_fingerprint()`].
+    ///
+    /// [`Solver::input_fingerprint()`]: crate::solver::Solver::input_fingerprint
+    pub fn hash_into(&self, hasher: &mut Sha256) -> error::Result<()> {
+        hasher.update(serde_json::to_vec(&self.items)?);
+        hasher.update(serde_json::to_vec(&self.aliases)?);
+
+        Ok(())
+    }
+
+    /// Returns the item with the given ID or alias.
+    pub fn get<Id>(&self, item_id: Id) -> Option<Item<'_>>
+    where
+        Id: AsRef<str>,
+    {
+        let id = self
+            .aliases
+            .get(item_id.as_ref())
+            .map_or(item_id.as_ref(), String::as_str);
+
+        let Some(raw_item) = self.items.get(id) else {
+            warn!("Could not find item with ID '{}'", item_id.as_ref());
+            return None;
+        };
+
+        Item::from_raw(self, raw_item).ok()
+    }
+
+    /// Returns every item in the catalog, sorted by ID.
+    pub fn iter(&self) -> impl Iterator<Item = Item<'_>> {
+        self.items
+            .values()
+            .filter_map(|raw_item| Item::from_raw(self, raw_item).ok())
+    }
+
+    /// Returns every raw resource (R0 item) in the catalog, sorted by ID. These are the IDs that may
+    /// be listed as a planet's resources in a system file.
+    pub fn resources(&self) -> Vec<Item<'_>> {
+        self.items
+            .values()
+            .filter(|raw_item| raw_item.tier == Tier::R0)
+            .filter_map(|raw_item| Item::from_raw(self, raw_item).ok())
+            .collect()
+    }
+
+    /// Returns every item along with its recipe and the items that consume it, sorted by ID. This
+    /// is the full production graph as an adjacency list, independent of any system.
+    pub fn graph(&self) -> Vec<GraphNode<'_>> {
+        self.items
+            .values()
+            .map(|raw_item| GraphNode {
+                id: &raw_item.id,
+                label: &raw_item.label,
+                tier: raw_item.tier,
+                quantity: raw_item.production.as_ref().map(|p| p.quantity),
+                inputs: raw_item
+                    .production
+                    .iter()
+                    .flat_map(|production| &production.inputs)
+                    .map(|(id, amount)| {
+                        let id = self.aliases.get(id).unwrap_or(id);
+                        (id.as_str(), *amount)
+                    })
+                    .collect(),
+                used_in: self
+                    .used_in
+                    .get(&raw_item.id)
+                    .into_iter()
+                    .flatten()
+                    .map(String::as_str)
+                    .collect(),
+            })
+            .sorted_by_key(|node| node.id)
+            .collect()
+    }
+
+    /// Returns every item that consumes `item`, or `None` if nothing does. Consumers that cannot
+    /// be loaded are skipped with a warning rather than failing.
+    pub fn get_products<'a>(&self, item: &'a Item<'a>) -> Option<Vec<Item<'_>>> {
+        let products = self.used_in.get(item.id)?;
+
+        let products = products
+            .iter()
+            .filter_map(|id| {
+                let product = self.get(id);
+
+                if product.is_none() {
+                    warn!(
+                        "Skipping {id}, a consumer of {}, as it could not be loaded",
+                        item.id
+                    );
+                }
+
+                product
+            })
+            .collect();
+
+        Some(products)
+    }
+
+    /// Returns every item that consumes the item with the given ID, directly or through any number
+    /// of intermediate products. Returns an empty set if no such item exists.
+    pub fn downstream_products(&self, id: &str) -> BTreeSet<Item<'_>> {
+        let mut products = BTreeSet::new();
+        let mut pending: Vec<&str> = self.get(id).map(|item| item.id).into_iter().collect();
+
+        while let Some(id) = pending.pop() {
+            for product_id in self.used_in.get(id).into_iter().flatten() {
+                let Some(product) = self.get(product_id) else {
+                    continue;
+                };
+
+                if products.insert(product.clone()) {
+                    pending.push(product.id);
+                }
+            }
+        }
+
+        products
+    }
+
+    /// Returns the number of production stages between the item with the given ID and the raw
+    /// resources it is ultimately made from, following the longest path through its inputs. R0
+    /// resources have a depth of 0.
+    ///
+    /// Fails if no such item exists, or if the item's chain contains a cycle.
+    pub fn chain_depth(&self, id: &str) -> error::Result<usize> {
+        self.chain_depth_from(id, &mut Vec::new())
+    }
+
+    /// Computes [`ItemManager::chain_depth()`] from the raw item definitions, since an [`Item`]
+    /// cannot be built for an item whose chain is cyclic. `visiting` holds the IDs of the items
+    /// currently being computed, so an item reached again from its own chain is a cycle.
+    fn chain_depth_from<'s>(
+        &'s self,
+        id: &'s str,
+        visiting: &mut Vec<&'s str>,
+    ) -> error::Result<usize> {
+        let id = self.aliases.get(id).map_or(id, String::as_str);
+        let raw_item = self
+            .items
+            .get(id)
+            .ok_or_else(|| Error::create_missing_item(id))?;
+
+        let Some(production) = &raw_item.production else {
+            return Ok(0);
+        };
+
+        if visiting.contains(&id) {
+            return Err(Error::ProductionCycle(id.to_string()));
+        }
+
+        visiting.push(id);
+
+        let mut depth = 0;
+
+        for input in production.inputs.keys() {
+            depth = depth.max(self.chain_depth_from(input, visiting)?);
+        }
+
+        visiting.pop();
+
+        Ok(depth + 1)
+    }
+
+    /// Returns every item that consumes `item`, alongside the amount of `item` each one requires
+    /// per production cycle.
+    pub fn get_products_with_usage(&self, item: &Item) -> Vec<(Item<'_>, u16)> {
+        let Some(products) = self.used_in.get(item.id) else {
+            return Vec::new();
+        };
+
+        products
+            .iter()
+            .filter_map(|id| self.get(id))
+            .filter_map(|product| {
+                let amount = product
+                    .production
+                    .as_ref()?
+                    .inputs
+                    .iter()
+                    .find(|input| input.item == *item)?
+                    .amount;
+
+                Some((product, amount))
+            })
+            .collect()
+    }
+}
+
+/// The bundled `examples/items.yaml`, compiled into the binary.
+const DEFAULT_ITEMS: &str = include_str!("../examples/items.yaml");
+
+/// Loads the item definitions bundled with the crate, for use when no items file is available.
+pub fn default_items() -> error::Result<ItemManager> {
+    DEFAULT_ITEMS.parse()
+}
+
+/// Parses an items file from a string rather than reading it from disk. Any includes are resolved
+/// relative to the current directory.
+impl FromStr for ItemManager {
+    type Err = Error;
+
+    fn from_str(contents: &str) -> error::Result<Self> {
+        let mut items = ItemMap::new();
+        load_items(contents, Path::new(""), &mut items, &mut HashSet::new())?;
+
+        Self::from_items(items, "<string>")
+    }
+}
+
+/// Reads and merges the items defined in each of `paths`. Returns the items along with the paths
+/// joined for use in error messages.
+fn load_items_files<I, P>(paths: I) -> error::Result<(ItemMap, String)>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut items = ItemMap::new();
+    let mut loaded = HashSet::new();
+    let mut sources = Vec::new();
+
+    for path in paths {
+        load_items_file(path.as_ref(), &mut items, &mut loaded)?;
+        sources.push(path.as_ref().display().to_string());
+    }
+
+    Ok((items, sources.join(", ")))
+}
+
+/// Reads the items defined in `path` into `items`, followed by the items of any files it includes.
+/// Include paths are resolved relative to the directory of the file that includes them.
+fn load_items_file(
+    path: &Path,
+    items: &mut ItemMap,
+    loaded: &mut HashSet<PathBuf>,
+) -> error::Result<()> {
+    let canonical = if input::is_stdin(path) {
+        path.to_path_buf()
+    } else {
+        fs::canonicalize(path)?
+    };
+
+    if !loaded.insert(canonical) {
+        warn!("Skipping {} as it has already been loaded", path.display());
+        return Ok(());
+    }
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    load_items(&input::read_to_string(path)?, directory, items, loaded)?;
+
+    debug!("Loaded items file {}", path.display());
+
+    Ok(())
+}
+
+/// Parses the items defined in `contents` into `items`, followed by the items of any files it
+/// includes, resolved relative to `directory`.
+fn load_items(
+    contents: &str,
+    directory: &Path,
+    items: &mut ItemMap,
+    loaded: &mut HashSet<PathBuf>,
+) -> error::Result<()> {
+    let raw: RawItemFile = serde_yaml::from_str(contents)?;
+
+    for (id, item) in raw.items {
+        match items.entry(id) {
+            Entry::Occupied(entry) => return Err(Error::DuplicateItem(entry.key().clone())),
+            Entry::Vacant(entry) => {
+                entry.insert(item);
+            }
+        }
+    }
+
+    for include in raw.includes {
+        load_items_file(&directory.join(include), items, loaded)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct RawItemFile {
+    #[serde(default)]
+    includes: Vec<PathBuf>,
+    #[serde(flatten)]
+    items: ItemMap,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct RawItem {
+    #[serde(default)]
+    id: String,
+    label: String,
+    tier: Tier,
+    /// Alternative IDs that may be used to refer to the item.
+    #[serde(default)]
+    aliases: Vec<String>,
+    production: Option<RawProduction>,
+    #[serde(default)]
+    value: f64,
+    #[serde(default)]
+    is_p4_input: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct RawProduction {
+    quantity: u16,
+    inputs: BTreeMap<String, u16>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_production_is_rejected() {
+        let result = ItemManager::from_str(
+            "
+a: { label: A, tier: r0 }
+b: { label: B, tier: p1, production: { quantity: 20, inputs: {} } }
+",
+        );
+
+        assert!(matches!(result, Err(Error::EmptyProduction(id)) if id == "b"));
+    }
+
+    #[test]
+    fn merge_inputs_sums_and_saturates() {
+        let item_manager = ItemManager::from_str(
+            "
+a: { label: A, tier: r0 }
+b: { label: B, tier: r0 }
+",
+        )
+        .unwrap();
+
+        let input = |id, amount| Input {
+            item: item_manager.get(id).unwrap(),
+            amount,
+        };
+
+        let merged: Vec<_> = merge_inputs(vec![
+            input("a", 10),
+            input("b", u16::MAX),
+            input("a", 30),
+            input("b", 1),
+        ])
+        .into_iter()
+        .map(|input| (input.item.id, input.amount))
+        .collect();
+
+        assert_eq!(merged, [("a", 40), ("b", u16::MAX)]);
+    }
+
+    #[test]
+    fn loading_several_inputs_merges_and_rejects_duplicates() {
+        let load = |inputs: &[&str]| {
+            let mut items = ItemMap::new();
+            let mut loaded = HashSet::new();
+
+            for contents in inputs {
+                load_items(contents, Path::new(""), &mut items, &mut loaded)?;
+            }
+
+            Ok::<_, Error>(items)
+        };
+
+        let first = "a: { label: A, tier: r0 }";
+        let items = load(&[first, "b: { label: B, tier: r0 }"]).unwrap();
+        assert_eq!(items.keys().collect::<Vec<_>>(), ["a", "b"]);
+
+        let result = load(&[first, "a: { label: Another A, tier: r0 }"]);
+        assert!(matches!(result, Err(Error::DuplicateItem(id)) if id == "a"));
+    }
+
+    #[test]
+    fn get_products_skips_missing_consumers() {
+        let mut item_manager = ItemManager::from_str(
+            "
+a: { label: A, tier: r0 }
+pa: { label: PA, tier: p1, production: { quantity: 20, inputs: { a: 3000 } } }
+",
+        )
+        .unwrap();
+
+        item_manager
+            .used_in
+            .entry("a".to_string())
+            .or_default()
+            .insert("missing".to_string());
+
+        let a = item_manager.get("a").unwrap();
+        let products = item_manager.get_products(&a).unwrap();
+
+        assert_eq!(
+            products.iter().map(|item| item.id).collect::<Vec<_>>(),
+            ["pa"]
+        );
+    }
+
+    #[test]
+    fn cyclic_items_are_left_out_when_unchecked() {
+        let mut items = ItemMap::new();
+        let contents = "
+a: { label: A, tier: r0 }
+x: { label: X, tier: p1, production: { quantity: 1, inputs: { a: 1, y: 1 } } }
+y: { label: Y, tier: p1, production: { quantity: 1, inputs: { x: 1 } } }
+";
+
+        load_items(contents, Path::new(""), &mut items, &mut HashSet::new()).unwrap();
+        let (item_manager, problems) = ItemManager::from_items_unchecked(items, "<string>");
+
+        assert!(matches!(
+            problems[..],
+            [Error::ProductionCycle(_), Error::ProductionCycle(_)]
+        ));
+        assert!(item_manager.get("x").is_none());
+        assert!(item_manager.get("a").is_some());
+    }
+}
@@ -0,0 +1,39 @@
+use clap::ValueEnum;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+pub mod error;
+pub mod facility;
+pub mod input;
+pub mod item;
+pub mod preset;
+pub mod solver;
+pub mod system;
+
+/// The tier of an item. Besides its name, each tier can be written as "tierN", and R0 as "raw" or
+/// "base", ignoring case both on the command line and in data files.
+#[derive(Debug, Serialize, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    #[value(alias = "tier0", alias = "raw", alias = "base")]
+    R0,
+    #[value(alias = "tier1")]
+    P1,
+    #[value(alias = "tier2")]
+    P2,
+    #[value(alias = "tier3")]
+    P3,
+    #[value(alias = "tier4")]
+    P4,
+}
+
+impl<'de> Deserialize<'de> for Tier {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+
+        <Self as ValueEnum>::from_str(&value, true)
+            .map_err(|_| de::Error::custom(format!("unknown tier '{value}'")))
+    }
+}
@@ -1,95 +1,1240 @@
-use crate::cli::Cli;
-use crate::item::ItemManager;
-use crate::solver::Solver;
-use crate::system::System;
-use clap::{Parser, ValueEnum};
+use crate::cli::{
+    Cli, Command, DownstreamArgs, Format, GroupBy, ItemsArgs, LintArgs, ScoutArgs, ScoutMetric,
+    SolveArgs, SortBy, SystemArgs, DEFAULT_ITEMS_PATH, ITEMS_ENV_VAR,
+};
+use crate::color::Painter;
+use clap::Parser;
 use itertools::Itertools;
-use serde::Deserialize;
+use log::LevelFilter;
+use pi2::item::{self, Item, ItemManager};
+use pi2::preset::Preset;
+use pi2::solver::{
+    Cycle, FactorySolution, MinimalPlanets, PresentableSolutions, Simulation, SimulationDiff,
+    Solution, SolutionMode, Solver, Versioned,
+};
+use pi2::system::{Scan, System};
+use pi2::{error, Tier};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 mod cli;
-mod error;
-mod item;
-mod solver;
-mod system;
+mod color;
+#[cfg(feature = "table")]
+mod render;
+#[cfg(feature = "repl")]
+mod repl;
+#[cfg(feature = "watch")]
+mod watch;
 
-fn main() -> Result<(), error::Error> {
-    env_logger::init();
+/// Returned when the inputs loaded successfully, but no solutions were left after filtering.
+const EXIT_NO_SOLUTIONS: u8 = 2;
 
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    init_logger(&cli);
 
-    let item_manager = ItemManager::new(cli.items)?;
-    let system = System::new(cli.system, &item_manager)?;
+    let result = match (cli.command, cli.args) {
+        #[cfg(feature = "repl")]
+        (Some(Command::Repl(args)), _) => with_preset(*args)
+            .and_then(|args| repl::run(&args))
+            .map(|_| ExitCode::SUCCESS),
+        (Some(Command::Graph(args)), _) => graph(&args),
+        (Some(Command::Resources(args)), _) => resources(&args),
+        (Some(Command::Downstream(args)), _) => downstream(&args),
+        (Some(Command::Info(args)), _) => info(&args),
+        (Some(Command::Scout(args)), _) => scout(&args),
+        (Some(Command::Lint(args)), _) => lint(&args),
+        (None, Some(args)) => with_preset(args).and_then(|args| {
+            #[cfg(feature = "watch")]
+            if args.watch {
+                return watch::run(&args).map(|_| ExitCode::SUCCESS);
+            }
 
-    let planets = if cli.include_planet.is_empty() {
-        system.planets
-    } else {
-        system
-            .planets
-            .into_iter()
-            .filter(|p| cli.include_planet.contains(&p.label))
-            .collect()
+            solve(&args)
+        }),
+        // Clap requires the solver arguments whenever no subcommand is given.
+        (None, None) => unreachable!(),
     };
 
-    let simulation = Solver::builder()
-        .use_factory_planet(!cli.no_factory)
-        .max_planets(cli.max_planets)
-        .production_max_tier(cli.production_max_tier)
-        .factory_max_tier(cli.factory_max_tier)
-        .build()
-        .solve(&planets, &item_manager);
+    result.unwrap_or_else(|error| {
+        eprintln!("Error: {error}");
+        ExitCode::FAILURE
+    })
+}
+
+/// Applies the preset named by `--preset`, if any.
+fn with_preset(mut args: SolveArgs) -> error::Result<SolveArgs> {
+    if let Some(name) = &args.preset {
+        let preset = Preset::load(&args.presets, name)?;
+        args.apply_preset(preset);
+    }
 
-    if !simulation.factory_solutions.is_empty() {
-        let min_tier = cli.factory_min_tier.unwrap_or(Tier::R0);
+    Ok(args)
+}
 
-        for solution in simulation.factory_solutions {
-            let products: Vec<_> = solution
-                .products
-                .into_iter()
-                .filter(|p| p.tier >= min_tier)
-                .sorted_by_key(|p| p.tier)
-                .rev()
-                .collect();
+/// Removes every planet from `system` that does not extract all of the resources with the IDs in
+/// `ids`.
+fn retain_planets_with_resources(
+    system: &mut System,
+    ids: &[String],
+    item_manager: &ItemManager,
+) -> error::Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let required: Vec<Item> = ids
+        .iter()
+        .map(|id| {
+            item_manager
+                .get(id)
+                .ok_or_else(|| error::Error::create_missing_item(id))
+        })
+        .collect::<error::Result<_>>()?;
+
+    system.planets.retain(|planet| {
+        let resources = planet.collect_resources();
+        required.iter().all(|item| resources.contains(item))
+    });
+
+    if system.planets.is_empty() {
+        eprintln!(
+            "Warning: no planet has all of the resources given to --planet-has: {}",
+            ids.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns [`error::Error::MissingItem`] for the first of `ids` that isn't a known item, so that a
+/// mistyped ID given to an option isn't silently ignored.
+fn require_items(ids: &[String], item_manager: &ItemManager) -> error::Result<()> {
+    match ids
+        .iter()
+        .find(|id| item_manager.get(id.as_str()).is_none())
+    {
+        Some(id) => Err(error::Error::create_missing_item(id)),
+        None => Ok(()),
+    }
+}
+
+/// Logs at the level given by `--quiet` or `--verbose`, or as configured by `RUST_LOG` if neither
+/// is given.
+fn init_logger(cli: &Cli) {
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => {
+            env_logger::init();
+            return;
+        }
+        (false, 1) => LevelFilter::Warn,
+        (false, 2) => LevelFilter::Info,
+        (false, 3) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+
+    env_logger::Builder::new().filter_level(level).init();
+}
+
+fn solve(args: &SolveArgs) -> error::Result<ExitCode> {
+    let item_manager = load_items(&args.input.items.items)?;
+    let scan = args.input.scan.as_ref().map(Scan::new).transpose()?;
+    let system = load_system(args, scan.as_ref(), &item_manager)?;
+
+    if let Some(label) = &args.factory_planet {
+        if !system.planets.iter().any(|planet| &planet.label == label) {
+            return Err(error::Error::MissingPlanet(label.clone()));
+        }
+    }
+
+    require_items(&args.import, &item_manager)?;
+    require_items(&args.exclude_item, &item_manager)?;
+
+    let solver = build_solver(args);
+
+    for query in solver.unmatched_include_planets(&system) {
+        eprintln!("Warning: --include-planet '{query}' did not match any planet");
+    }
+
+    if !args.minimize.is_empty() {
+        return Ok(minimize(args, &solver, &system, &item_manager));
+    }
+
+    if let Some(counts) = &args.max_planets_range {
+        return solve_for_counts(args, &solver, counts.clone(), &system, &item_manager);
+    }
+
+    if args.format == Format::Jsonl {
+        return stream_jsonl(args, &solver, &system, &item_manager);
+    }
+
+    let mut simulation = solver.solve(&system, &item_manager);
+
+    if args.best_planet {
+        simulation.retain_best_planet();
+    }
+
+    if let Some(other) = &args.diff {
+        let other = System::new(other, scan.as_ref(), &item_manager)?;
+        let other = solver.solve(&other, &item_manager);
+
+        print_diff(&simulation.diff(&other));
+
+        let min_tier = args.factory_min_tier();
+        let solved = [&simulation, &other]
+            .iter()
+            .any(|simulation| !simulation.presentable_solutions(min_tier).is_empty());
 
-            if products.is_empty() {
+        return if solved {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Ok(ExitCode::from(EXIT_NO_SOLUTIONS))
+        };
+    }
+
+    match args.format {
+        Format::Text => {
+            if let Some(note) = &args.note {
+                println!("Note: {note}");
+                println!();
+            }
+
+            if let Some(tier) = simulation.skill_max_tier {
+                println!("Limited to {tier:?} by skill level");
+                println!();
+            }
+
+            match args.group_by {
+                Some(GroupBy::Tier) => print_grouped_by_tier(&simulation, args),
+                None => print_text(&simulation, args),
+            }
+
+            if args.report_unreachable {
+                let unreachable = simulation.unreachable_products(&item_manager);
+                println!("Unreachable products ({}):", unreachable.len());
+
+                for id in unreachable {
+                    let is_blocked = item_manager
+                        .get(id)
+                        .is_some_and(|item| simulation.is_blocked_by_skill(item.tier));
+
+                    if is_blocked {
+                        println!("  {id} (blocked by skill level)");
+                    } else {
+                        println!("  {id}");
+                    }
+                }
+            }
+
+            if args.report_coverage {
+                let (reached, total) = simulation.tier_coverage(Tier::P4, &item_manager);
+
+                println!(
+                    "P4 coverage: {reached}/{total} ({:.0}%)",
+                    simulation.p4_coverage(&item_manager) * 100.0
+                );
+            }
+
+            if args.diagnose {
+                print_diagnosis(&simulation, &solver, &item_manager);
+            }
+
+            if args.report_frequency {
+                println!("Planet frequency:");
+
+                for (label, count) in simulation.planet_frequency() {
+                    println!("  {label}: {count}");
+                }
+            }
+
+            if args.report_non_contributing {
+                let planets = simulation.non_contributing_planets();
+                println!("Non-contributing planets ({}):", planets.len());
+
+                for label in planets {
+                    println!("  {label}");
+                }
+            }
+
+            if args.report_factory_delta {
+                print_factory_delta(&simulation, &solver, &system, &item_manager, args);
+            }
+
+            if args.show_cycles {
+                print_cycles(&simulation, &solver, &item_manager, args);
+            }
+        }
+        Format::Toml => print!(
+            "{}",
+            toml::to_string(&Versioned::new(&simulation).with_note(args.note.clone()))?
+        ),
+        Format::Yaml => print!(
+            "{}",
+            serde_yaml::to_string(&Versioned::new(&simulation).with_note(args.note.clone()))
+                .map_err(error::Error::YamlSerialize)?
+        ),
+        #[cfg(feature = "table")]
+        Format::Table => println!("{}", render::table(&simulation, args)),
+        Format::Ids => print_ids(&simulation, args),
+        Format::Jsonl => unreachable!(),
+    }
+
+    let min_tier = args.factory_min_tier();
+
+    if simulation.presentable_solutions(min_tier).is_empty() {
+        Ok(ExitCode::from(EXIT_NO_SOLUTIONS))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn graph(args: &ItemsArgs) -> error::Result<ExitCode> {
+    let item_manager = load_items(&args.items)?;
+    println!("{}", serde_json::to_string_pretty(&item_manager.graph())?);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn resources(args: &ItemsArgs) -> error::Result<ExitCode> {
+    let item_manager = load_items(&args.items)?;
+
+    for resource in item_manager.resources() {
+        println!("{}: {}", resource.id, resource.label);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn downstream(args: &DownstreamArgs) -> error::Result<ExitCode> {
+    let item_manager = load_items(&args.items.items)?;
+
+    if item_manager.get(&args.item).is_none() {
+        return Err(error::Error::MissingItem(args.item.clone()));
+    }
+
+    let products = item_manager.downstream_products(&args.item);
+
+    for product in products.iter().sorted_by_key(|p| (p.tier, p.label)) {
+        println!("{:?} {}", product.tier, product.label);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn info(args: &SystemArgs) -> error::Result<ExitCode> {
+    let item_manager = load_items(&args.items.items)?;
+    let scan = args.scan.as_ref().map(Scan::new).transpose()?;
+    let system = System::new(&args.system, scan.as_ref(), &item_manager)?;
+
+    println!("{system}");
+
+    let highest_tier = [Tier::P4, Tier::P3, Tier::P2, Tier::P1]
+        .into_iter()
+        .find(|tier| system.can_produce_tier(*tier, &item_manager));
+
+    if let Some(tier) = highest_tier {
+        println!("Can produce up to {tier:?}");
+    }
+
+    for planet in &system.planets {
+        println!();
+        print!("{planet}");
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn scout(args: &ScoutArgs) -> error::Result<ExitCode> {
+    let item_manager = load_items(&args.items.items)?;
+    let solver = Solver::builder()
+        .max_planets(args.max_planets)
+        .show_progress(false)
+        .build();
+
+    let mut paths: Vec<_> = fs::read_dir(&args.dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .filter_ok(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+        .collect::<io::Result<_>>()?;
+
+    paths.sort();
+
+    let mut rows = Vec::new();
+
+    for path in paths {
+        let system = match System::new(&path, None, &item_manager) {
+            Ok(system) => system,
+            Err(error) => {
+                log::warn!("Skipping {}: {error}", path.display());
                 continue;
             }
+        };
 
-            println!(
-                "Using {}",
-                solution.planets.iter().map(|s| &s.planet.label).join(", ")
-            );
+        let simulation = solver.solve(&system, &item_manager);
+        let (reached, total) = simulation.tier_coverage(Tier::P4, &item_manager);
+
+        rows.push((
+            system.label.clone(),
+            simulation.max_tier(),
+            simulation.p4_coverage(&item_manager),
+            format!("{reached}/{total}"),
+        ));
+    }
+
+    let by_coverage = |a: f32, b: f32| b.total_cmp(&a);
+
+    rows.sort_by(|a, b| {
+        match args.sort {
+            ScoutMetric::P4Coverage => by_coverage(a.2, b.2).then_with(|| b.1.cmp(&a.1)),
+            ScoutMetric::MaxTier => b.1.cmp(&a.1).then_with(|| by_coverage(a.2, b.2)),
+        }
+        .then_with(|| a.0.cmp(&b.0))
+    });
+
+    let width = rows
+        .iter()
+        .map(|(label, ..)| label.len())
+        .max()
+        .unwrap_or_default()
+        .max("System".len());
+
+    println!("{:width$}  Max tier  P4 coverage", "System");
+
+    for (label, max_tier, coverage, counts) in rows {
+        let max_tier = max_tier.map_or("-".to_owned(), |tier| format!("{tier:?}"));
+        println!(
+            "{label:width$}  {max_tier:8}  {counts} ({:.0}%)",
+            coverage * 100.0
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// A simulation solved by `--max-planets-range`, along with the planet count it was solved for.
+#[derive(Serialize)]
+struct CountedSimulation<'s, 'a> {
+    max_planets: usize,
+    #[serde(flatten)]
+    simulation: &'s Simulation<'a>,
+}
+
+/// Every simulation solved by `--max-planets-range`, serialized as a single document.
+#[derive(Serialize)]
+struct CountedSimulations<'s, 'a> {
+    simulations: Vec<CountedSimulation<'s, 'a>>,
+}
+
+fn solve_for_counts(
+    args: &SolveArgs,
+    solver: &Solver,
+    counts: RangeInclusive<usize>,
+    system: &System,
+    item_manager: &ItemManager,
+) -> error::Result<ExitCode> {
+    let min_tier = args.factory_min_tier();
+    let simulations = solver.solve_for_counts(system, item_manager, counts);
+    let counted = || {
+        simulations
+            .iter()
+            .map(|(count, simulation)| CountedSimulation {
+                max_planets: *count,
+                simulation,
+            })
+    };
+
+    match args.format {
+        Format::Toml | Format::Yaml => {
+            let document = Versioned::new(CountedSimulations {
+                simulations: counted().collect(),
+            })
+            .with_note(args.note.clone());
+
+            if args.format == Format::Toml {
+                print!("{}", toml::to_string(&document)?);
+            } else {
+                print!(
+                    "{}",
+                    serde_yaml::to_string(&document).map_err(error::Error::YamlSerialize)?
+                );
+            }
+        }
+        Format::Jsonl => {
+            let mut stdout = io::stdout().lock();
+
+            for simulation in counted() {
+                write_json_line(&mut stdout, &simulation, args.note.as_deref())?;
+            }
+        }
+        _ => {
+            for (count, simulation) in &simulations {
+                println!("== {count} planet(s) ==");
+                println!();
 
-            for product in products {
-                if product.tier < min_tier {
+                // Otherwise nothing at all would be printed for this count.
+                if simulation.best_mode() == SolutionMode::Factory
+                    && simulation.factory_solutions.is_empty()
+                {
+                    println!("No factory solutions");
+                    println!();
                     continue;
                 }
 
-                println!("  {product}");
+                match args.format {
+                    #[cfg(feature = "table")]
+                    Format::Table => println!("{}", render::table(simulation, args)),
+                    Format::Ids => print_ids(simulation, args),
+                    _ => print_text(simulation, args),
+                }
             }
-
-            println!();
         }
+    }
+
+    let solved = simulations
+        .iter()
+        .any(|(_, simulation)| !simulation.presentable_solutions(min_tier).is_empty());
+
+    if solved {
+        Ok(ExitCode::SUCCESS)
     } else {
-        for solution in simulation.planet_solutions {
-            println!("{}", solution.planet);
+        Ok(ExitCode::from(EXIT_NO_SOLUTIONS))
+    }
+}
 
-            for product in solution.products.iter().sorted_by_key(|p| p.tier) {
-                println!("  {product}");
+fn minimize(
+    args: &SolveArgs,
+    solver: &Solver,
+    system: &System,
+    item_manager: &ItemManager,
+) -> ExitCode {
+    let targets: Vec<_> = args.minimize.iter().map(String::as_str).collect();
+
+    let Some(minimal) = solver.minimize_planets(&targets, system, item_manager) else {
+        println!("No set of planets can produce {}", targets.join(", "));
+        return ExitCode::from(EXIT_NO_SOLUTIONS);
+    };
+
+    println!(
+        "{} planet(s) can produce {}:",
+        minimal.planet_count(),
+        targets.join(", ")
+    );
+
+    match minimal {
+        MinimalPlanets::PerPlanet(solutions) => {
+            for solution in solutions {
+                println!("  {}", solution.planet.label);
+            }
+        }
+        MinimalPlanets::Factory(solution) => {
+            for production in &solution.planets {
+                println!("  {}", production.planet.label);
             }
 
-            println!();
+            match solution.factory_planet {
+                Some(planet) => println!("  {} (factory)", planet.label),
+                None => println!("  Any other planet as the factory"),
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// Prints each solution as a single line of JSON as soon as it is solved. Factory solutions are
+/// streamed while solving; per-planet solutions are only printed when the factory is disabled,
+/// and only the best one with `--best-planet`. Nothing more is printed once `--top` solutions
+/// have been.
+fn stream_jsonl(
+    args: &SolveArgs,
+    solver: &Solver,
+    system: &System,
+    item_manager: &ItemManager,
+) -> error::Result<ExitCode> {
+    let mut stdout = io::stdout().lock();
+    let top = args.top.unwrap_or(usize::MAX);
+    let mut printed = 0;
+
+    let planet_solutions = solver.solve_streaming(system, item_manager, |solution| {
+        if printed >= top {
+            return Ok(());
         }
+
+        printed += 1;
+        write_json_line(&mut stdout, &solution, args.note.as_deref())
+    })?;
+
+    if solver.mode() == SolutionMode::PerPlanet {
+        let planet_solutions = if args.best_planet {
+            planet_solutions
+                .iter()
+                .max_by(|a, b| a.compare_reach(b))
+                .into_iter()
+                .collect()
+        } else {
+            planet_solutions.iter().collect::<Vec<_>>()
+        };
+
+        for solution in planet_solutions.into_iter().take(top) {
+            printed += 1;
+            write_json_line(&mut stdout, solution, args.note.as_deref())?;
+        }
+    }
+
+    if printed == 0 {
+        Ok(ExitCode::from(EXIT_NO_SOLUTIONS))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn lint(args: &LintArgs) -> error::Result<ExitCode> {
+    let paths = items_paths(&args.items.items);
+
+    let (item_manager, item_problems) = if paths.is_empty() {
+        (item::default_items()?, Vec::new())
+    } else {
+        let (item_manager, problems) = ItemManager::lint(&paths)?;
+        let source = paths.iter().map(|path| path.display()).join(", ");
+
+        (
+            item_manager,
+            problems.into_iter().map(|p| (source.clone(), p)).collect(),
+        )
+    };
+
+    let system_problems = System::lint(&args.system, &item_manager)?
+        .into_iter()
+        .map(|problem| (args.system.display().to_string(), problem));
+
+    let problems: Vec<_> = item_problems.into_iter().chain(system_problems).collect();
+
+    for (source, problem) in &problems {
+        println!("{source}: {problem}");
     }
 
+    if problems.is_empty() {
+        println!("No problems found");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!("Found {} problem(s)", problems.len());
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn write_json_line<W, T>(writer: &mut W, value: &T, note: Option<&str>) -> error::Result<()>
+where
+    W: Write,
+    T: Serialize,
+{
+    let value = Versioned::new(value).with_note(note.map(str::to_string));
+    serde_json::to_writer(&mut *writer, &value)?;
+    writeln!(writer)?;
+
     Ok(())
 }
 
-#[derive(Debug, Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, ValueEnum)]
-#[serde(rename_all = "lowercase")]
-pub enum Tier {
-    R0,
-    P1,
-    P2,
-    P3,
-    P4,
+/// Loads the system file along with any given by `--with-system`, keeping only the planets that
+/// have the resources given by `--planet-has`.
+fn load_system<'a>(
+    args: &SolveArgs,
+    scan: Option<&Scan>,
+    item_manager: &'a ItemManager,
+) -> error::Result<System<'a>> {
+    let others = args
+        .with_system
+        .iter()
+        .map(|path| System::new(path, scan, item_manager))
+        .collect::<error::Result<_>>()?;
+
+    let mut system = System::new(&args.input.system, scan, item_manager)?.merge(others);
+    retain_planets_with_resources(&mut system, &args.planet_has, item_manager)?;
+
+    Ok(system)
+}
+
+/// Loads and merges the items files given by `--items`. See [`items_paths()`].
+fn load_items(paths: &[PathBuf]) -> error::Result<ItemManager> {
+    let paths = items_paths(paths);
+
+    if paths.is_empty() {
+        item::default_items()
+    } else {
+        ItemManager::new(&paths)
+    }
+}
+
+/// Resolves the items files given by `--items`, falling back to the path in [`ITEMS_ENV_VAR`] and
+/// then [`DEFAULT_ITEMS_PATH`]. Returns no paths if the default path does not exist, in which case
+/// the bundled items should be used instead.
+fn items_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    if !paths.is_empty() {
+        return paths.to_vec();
+    }
+
+    if let Some(path) = env::var_os(ITEMS_ENV_VAR).filter(|path| !path.is_empty()) {
+        return vec![path.into()];
+    }
+
+    let path = Path::new(DEFAULT_ITEMS_PATH);
+    path.exists()
+        .then(|| path.to_path_buf())
+        .into_iter()
+        .collect()
+}
+
+fn build_solver(args: &SolveArgs) -> Solver {
+    // A P4 factory takes every P3 the production planets can make and imports the rest
+    let (production_max_tier, factory_max_tier, import_tier) = if args.p4_factory {
+        (
+            args.production_max_tier.or(Some(Tier::P3)),
+            Some(Tier::P4),
+            Some(Tier::P3),
+        )
+    } else {
+        (args.production_max_tier, args.factory_max_tier, None)
+    };
+
+    Solver::builder()
+        .use_factory_planet(!args.no_factory && !args.best_planet)
+        .max_planets(args.max_planets)
+        .production_max_tier(production_max_tier)
+        .factory_max_tier(factory_max_tier)
+        .max_tier(args.max_tier)
+        .skill_max_tier(args.skill_max_tier)
+        .include_planets(args.include_planet.clone())
+        .exclude_planets(args.exclude_planet.clone())
+        .max_jumps(args.max_jumps)
+        .exclude_items(args.exclude_item.clone())
+        .imports(args.import.clone())
+        .import_tier(import_tier)
+        .import_p4_inputs(!args.no_import_p4_inputs)
+        .max_cycles(args.max_cycles)
+        .factory_min_tier(args.factory_min_tier())
+        .factory_planet(args.factory_planet.clone())
+        .require_all_tiers(args.require_all_tiers)
+        .min_products(args.min_products)
+        .max_products_per_planet(args.max_products_per_planet)
+        // JSON lines are printed while solving, which would interleave with the progress bar
+        .show_progress(args.format != Format::Jsonl)
+        .build()
+}
+
+fn print_text(simulation: &Simulation, args: &SolveArgs) {
+    let painter = Painter::new(args.color);
+    let top = args.top.unwrap_or(usize::MAX);
+    let min_tier = args.factory_min_tier();
+
+    match simulation.presentable_solutions(min_tier) {
+        PresentableSolutions::Factory(solutions) => {
+            for (solution, mut products) in sort_factory_solutions(solutions, args).take(top) {
+                sort_products(&mut products, args);
+                println!("Using {}", planet_labels(solution));
+
+                if let Some(planet) = solution.factory_planet {
+                    println!("Factory: {}", planet.label);
+                }
+
+                if args.show_cost {
+                    println!("Setup cost: {}", solution.facility_cost());
+                }
+
+                let resources = solution.required_resources();
+
+                println!(
+                    "Requires extracting ({}): {}",
+                    resources.len(),
+                    resources.iter().map(|r| r.label).sorted().join(", ")
+                );
+
+                if args.warn_shortfalls && !solution.balanced {
+                    println!(
+                        "Warning: inputs are under-supplied: {}",
+                        solution
+                            .shortfalls
+                            .iter()
+                            .map(|s| match s.ratio() {
+                                Some(ratio) => format!("{} ({ratio:.1}× supply)", s.item.label),
+                                None => format!("{} (not supplied)", s.item.label),
+                            })
+                            .join(", ")
+                    );
+                }
+
+                let redundant = if args.warn_redundant {
+                    solution.redundant_planets()
+                } else {
+                    Vec::new()
+                };
+
+                for planet in redundant {
+                    println!(
+                        "Warning: {} only makes products other planets already supply",
+                        planet.planet.label
+                    );
+                }
+
+                if args.unique_production {
+                    print_suggested_assignment(solution);
+                }
+
+                for planet in solution.planets.iter().filter(|s| !s.fits_powergrid) {
+                    println!(
+                        "Warning: {} lacks the CPU or powergrid for its facilities",
+                        planet.planet.label
+                    );
+                }
+
+                let resources = resources_if(args.show_resources, || solution.resources())
+                    .filter(|resource| resource.tier >= min_tier);
+
+                if args.p4_factory {
+                    let required = solution.required_imports();
+
+                    println!(
+                        "Requires importing ({}): {}",
+                        required.len(),
+                        required.iter().map(|i| i.label).sorted().join(", ")
+                    );
+                } else if !solution.imports.is_empty() {
+                    println!(
+                        "Imports: {}",
+                        solution.imports.iter().map(|i| i.label).sorted().join(", ")
+                    );
+                }
+
+                for product in products.into_iter().chain(resources) {
+                    let marker = if solution.depends_on_imports(product) {
+                        " (requires imports)"
+                    } else {
+                        ""
+                    };
+
+                    let marginal = args
+                        .warn_marginal
+                        .map(|threshold| solution.marginal_resources(product, threshold))
+                        .map(|resources| marginal_marker(&resources))
+                        .unwrap_or_default();
+
+                    let origins = args
+                        .trace_origin
+                        .then(|| solution.origins(product))
+                        .filter(|origins| !origins.is_empty())
+                        .map(|origins| format!(" [from {}]", origins.join(", ")))
+                        .unwrap_or_default();
+
+                    let yield_ratio = yield_marker(product, args);
+                    let depth = depth_marker(product, args);
+
+                    println!(
+                        "  {}{marker}{depth}{yield_ratio}{marginal}{origins}",
+                        painter.paint(product.tier, product.with_quantities(args.show_quantities))
+                    );
+
+                    let inputs = product
+                        .production
+                        .iter()
+                        .filter(|_| args.include_tier_below)
+                        .flat_map(|production| &production.inputs);
+
+                    for input in inputs {
+                        println!(
+                            "    {} ({:?})",
+                            painter.paint(input.item.tier, input.item.label),
+                            input.item.tier
+                        );
+                    }
+                }
+
+                println!();
+            }
+        }
+        PresentableSolutions::PerPlanet(solutions) => {
+            for (solution, products) in solutions.into_iter().take(top) {
+                print_planet_solution(solution, products, &painter, args);
+            }
+        }
+    }
+}
+
+fn print_planet_solution(
+    solution: &Solution,
+    mut products: Vec<&Item>,
+    painter: &Painter,
+    args: &SolveArgs,
+) {
+    sort_products(&mut products, args);
+
+    println!(
+        "{}",
+        solution
+            .planet
+            .with_extraction_rate(args.base_extraction_rate)
+            .with_max_tier(solution.max_producible_tier())
+    );
+
+    if args.show_cost {
+        println!("Setup cost: {}", solution.facility_cost());
+    }
+
+    if !solution.fits_powergrid {
+        println!("Warning: lacks the CPU or powergrid for these facilities");
+    }
+
+    let estimate = args
+        .base_extraction_rate
+        .map(|base_rate| solution.facility_estimate(base_rate, args.cycle_hours))
+        .unwrap_or_default();
+
+    for product in resources_if(args.show_resources, || solution.resources()).chain(products) {
+        let marginal = args
+            .warn_marginal
+            .map(|threshold| solution.marginal_resources(product, threshold))
+            .map(|resources| marginal_marker(&resources))
+            .unwrap_or_default();
+
+        let yield_ratio = yield_marker(product, args);
+        let depth = depth_marker(product, args);
+
+        match estimate.get(product.id) {
+            Some(facilities) => println!(
+                "  {} (~{facilities} facilities){depth}{yield_ratio}{marginal}",
+                painter.paint(product.tier, product.with_quantities(args.show_quantities))
+            ),
+            None => println!(
+                "  {}{depth}{yield_ratio}{marginal}",
+                painter.paint(product.tier, product.with_quantities(args.show_quantities))
+            ),
+        }
+    }
+
+    println!();
+}
+
+fn print_ids(simulation: &Simulation, args: &SolveArgs) {
+    let top = args.top.unwrap_or(usize::MAX);
+    let solutions: Vec<_> = match simulation.presentable_solutions(args.factory_min_tier()) {
+        PresentableSolutions::Factory(solutions) => sort_factory_solutions(solutions, args)
+            .map(|(_, products)| products)
+            .collect(),
+        PresentableSolutions::PerPlanet(solutions) => solutions
+            .into_iter()
+            .map(|(_, products)| products)
+            .collect(),
+    };
+
+    for mut products in solutions.into_iter().take(top) {
+        sort_products(&mut products, args);
+        println!("{}", products.iter().map(|product| product.id).join(","));
+    }
+}
+
+fn print_suggested_assignment(solution: &FactorySolution) {
+    let overlaps = solution.production_overlaps();
+
+    if overlaps.is_empty() {
+        return;
+    }
+
+    println!("Made on more than one planet ({}):", overlaps.len());
+
+    for (product, planet) in solution.suggested_assignment() {
+        let others = overlaps
+            .iter()
+            .find(|(item, _)| *item == product)
+            .into_iter()
+            .flat_map(|(_, planets)| planets)
+            .filter(|other| **other != planet)
+            .join(", ");
+
+        println!(
+            "  {}: assign to {planet} (also made on {others})",
+            product.label
+        );
+    }
+}
+
+/// Reorders `products` as chosen by `--sort-products`, if given.
+fn sort_products(products: &mut [&Item], args: &SolveArgs) {
+    if let Some(order) = args.sort_products {
+        products.sort_by(|a, b| order.compare(a, b));
+    }
+}
+
+/// Formats the annotation for a product's yield ratio, or an empty string if `--show-yield` wasn't
+/// given or the product is a raw resource.
+fn yield_marker(product: &Item, args: &SolveArgs) -> String {
+    product
+        .production
+        .as_ref()
+        .filter(|_| args.show_yield)
+        .map(|production| format!(" (yield {:.2})", production.yield_ratio()))
+        .unwrap_or_default()
+}
+
+/// Formats the annotation for a product's chain depth, or an empty string if `--show-depth` wasn't
+/// given or the product is a raw resource.
+fn depth_marker(product: &Item, args: &SolveArgs) -> String {
+    product
+        .production
+        .as_ref()
+        .filter(|_| args.show_depth)
+        .map(|_| format!(" (depth {})", product.chain_depth()))
+        .unwrap_or_default()
+}
+
+/// Formats the annotation for a product made from `resources` extracted at a marginal density, or
+/// an empty string if there are none.
+fn marginal_marker(resources: &[&Item]) -> String {
+    if resources.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " (marginal: {})",
+            resources.iter().map(|resource| resource.label).join(", ")
+        )
+    }
+}
+
+/// Prints each product once, under a heading for its tier, followed by every solution that
+/// produces it.
+fn print_grouped_by_tier(simulation: &Simulation, args: &SolveArgs) {
+    let painter = Painter::new(args.color);
+    let top = args.top.unwrap_or(usize::MAX);
+    let min_tier = args.factory_min_tier();
+
+    let solutions: Vec<(String, Vec<&Item>)> = match simulation.presentable_solutions(min_tier) {
+        PresentableSolutions::Factory(solutions) => sort_factory_solutions(solutions, args)
+            .take(top)
+            .map(|(solution, products)| (planet_labels(solution), products))
+            .collect(),
+        PresentableSolutions::PerPlanet(solutions) => solutions
+            .into_iter()
+            .take(top)
+            .map(|(solution, products)| (solution.planet.label.clone(), products))
+            .collect(),
+    };
+
+    let mut tiers: BTreeMap<Tier, BTreeMap<&str, Vec<&str>>> = BTreeMap::new();
+
+    for (label, products) in &solutions {
+        for product in products {
+            tiers
+                .entry(product.tier)
+                .or_default()
+                .entry(product.label)
+                .or_default()
+                .push(label);
+        }
+    }
+
+    for (tier, products) in tiers {
+        println!("{tier:?}");
+
+        for (product, producers) in products {
+            println!("  {}", painter.paint(tier, product));
+
+            for producer in producers {
+                println!("    {producer}");
+            }
+        }
+
+        println!();
+    }
+}
+
+/// Orders factory solutions according to `--sort`, then `--tiebreak`. With `--prefer-diverse`,
+/// solutions whose planets share the fewest resources come first, ahead of any other ordering.
+fn sort_factory_solutions<'s, T>(
+    solutions: Vec<(&'s FactorySolution<'s>, T)>,
+    args: &SolveArgs,
+) -> impl Iterator<Item = (&'s FactorySolution<'s>, T)> {
+    let sort = args.sort;
+    let prefer_diverse = args.prefer_diverse;
+    let tiebreak = args.tiebreak.clone();
+
+    solutions.into_iter().sorted_by(move |(a, _), (b, _)| {
+        let diversity = if prefer_diverse {
+            a.resource_overlap().cmp(&b.resource_overlap())
+        } else {
+            Ordering::Equal
+        };
+
+        diversity
+            .then_with(|| match sort {
+                Some(SortBy::Planets) => planet_labels(a).cmp(&planet_labels(b)),
+                Some(SortBy::Value) => b.estimated_value().total_cmp(&a.estimated_value()),
+                Some(SortBy::Tier) => max_tier(b).cmp(&max_tier(a)),
+                Some(SortBy::Density) => b.total_density().total_cmp(&a.total_density()),
+                Some(SortBy::Jumps) => a.total_jumps().cmp(&b.total_jumps()),
+                None => Ordering::Equal,
+            })
+            .then_with(|| match tiebreak.as_deref() {
+                Some(tiebreaks) => a.rank(b, tiebreaks),
+                None => Ordering::Equal,
+            })
+    })
+}
+
+fn planet_labels(solution: &FactorySolution) -> String {
+    solution.planets.iter().map(|s| &s.planet.label).join(", ")
+}
+
+fn max_tier(solution: &FactorySolution) -> Option<Tier> {
+    solution.products.iter().map(|product| product.tier).max()
+}
+
+fn print_diagnosis(simulation: &Simulation, solver: &Solver, item_manager: &ItemManager) {
+    let max_tier = solver.production_max_tier().max(solver.factory_max_tier());
+    let mut is_reachable = true;
+
+    println!("Diagnosis:");
+
+    for tier in [Tier::P1, Tier::P2, Tier::P3, Tier::P4] {
+        if tier > max_tier || simulation.tier_coverage(tier, item_manager).0 > 0 {
+            continue;
+        }
+
+        is_reachable = false;
+
+        if simulation.is_blocked_by_skill(tier) {
+            println!("  No {tier:?} products can be made (blocked by skill level)");
+            continue;
+        }
+
+        println!("  No {tier:?} products can be made:");
+
+        for (item, missing) in simulation.blocked_recipes(tier, item_manager) {
+            println!(
+                "    {} needs {}",
+                item.label,
+                missing.iter().map(|input| input.label).join(", ")
+            );
+        }
+    }
+
+    if is_reachable {
+        println!("  Every tier up to {max_tier:?} can be made");
+    }
+}
+
+fn print_factory_delta(
+    simulation: &Simulation,
+    solver: &Solver,
+    system: &System,
+    item_manager: &ItemManager,
+    args: &SolveArgs,
+) {
+    let PresentableSolutions::Factory(solutions) = simulation.presentable_solutions(Tier::R0)
+    else {
+        return;
+    };
+
+    let standalone = solver.standalone_products(system, item_manager);
+    let top = args.top.unwrap_or(usize::MAX);
+
+    println!("Factory-exclusive products:");
+
+    for (solution, _) in sort_factory_solutions(solutions, args).take(top) {
+        let exclusive = solution.exclusive_products(&standalone);
+
+        println!(
+            "  Using {} ({}): {}",
+            planet_labels(solution),
+            exclusive.len(),
+            exclusive.iter().map(|item| item.label).sorted().join(", ")
+        );
+    }
+}
+
+fn print_cycles(
+    simulation: &Simulation,
+    solver: &Solver,
+    item_manager: &ItemManager,
+    args: &SolveArgs,
+) {
+    let top = args.top.unwrap_or(usize::MAX);
+    let min_tier = args.factory_min_tier();
+
+    println!("Production cycles:");
+
+    match simulation.presentable_solutions(min_tier) {
+        PresentableSolutions::Factory(solutions) => {
+            for (solution, _) in sort_factory_solutions(solutions, args).take(top) {
+                let inputs = solver.factory_inputs(solution, item_manager);
+                let inputs = inputs.iter().collect();
+
+                let (_, cycles) =
+                    solver.solve_cycles_traced(&inputs, item_manager, solver.factory_max_tier());
+
+                println!("  Using {}", planet_labels(solution));
+                print_cycle_additions(&cycles);
+            }
+        }
+        PresentableSolutions::PerPlanet(solutions) => {
+            for (solution, _) in solutions.into_iter().take(top) {
+                let (_, cycles) = solver.solve_cycles_traced(
+                    &solution.planet.collect_resources(),
+                    item_manager,
+                    solver.production_max_tier(),
+                );
+
+                println!("  {}", solution.planet.label);
+                print_cycle_additions(&cycles);
+            }
+        }
+    }
+}
+
+fn print_cycle_additions(cycles: &[Cycle]) {
+    for (iteration, cycle) in cycles.iter().enumerate() {
+        if !cycle.added.is_empty() {
+            println!(
+                "    Iteration {} added: {}",
+                iteration + 1,
+                cycle
+                    .added
+                    .iter()
+                    .map(|item| item.label)
+                    .sorted()
+                    .join(", ")
+            );
+        }
+    }
+}
+
+fn print_diff(diff: &SimulationDiff) {
+    if diff.is_empty() {
+        println!("No products were gained or lost");
+        return;
+    }
+
+    for solution in &diff.solutions {
+        println!("{}", solution.planets.join(", "));
+
+        for product in &solution.added {
+            println!("  + {}", product.label);
+        }
+
+        for product in &solution.removed {
+            println!("  - {}", product.label);
+        }
+
+        println!();
+    }
+}
+
+fn resources_if<I, F>(show_resources: bool, resources: F) -> impl Iterator<Item = I::Item>
+where
+    I: IntoIterator,
+    F: FnOnce() -> I,
+{
+    show_resources.then(resources).into_iter().flatten()
 }
@@ -0,0 +1,51 @@
+use crate::error::{Error, Result};
+use crate::input;
+use crate::Tier;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// A named set of solver options, so that a frequently used combination of options doesn't need
+/// to be repeated. Every option may be left out, in which case it falls back to what would be
+/// used without the preset.
+///
+/// A presets file maps each preset's name to its options, such as the following.
+///
+/// ```yaml
+/// small-p2:
+///     max_planets: 3
+///     max_tier: p2
+///     exclude_planets: [J103326 VI]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    pub no_factory: Option<bool>,
+    pub max_planets: Option<usize>,
+    pub production_max_tier: Option<Tier>,
+    pub factory_max_tier: Option<Tier>,
+    pub max_tier: Option<Tier>,
+    pub factory_min_tier: Option<Tier>,
+    pub max_cycles: Option<usize>,
+    pub require_all_tiers: Option<bool>,
+    pub min_products: Option<usize>,
+    pub include_planets: Vec<String>,
+    pub exclude_planets: Vec<String>,
+    pub exclude_items: Vec<String>,
+    pub imports: Vec<String>,
+}
+
+impl Preset {
+    /// Loads the preset named `name` from the presets file at `presets_path`.
+    pub fn load<P>(presets_path: P, name: &str) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut presets: BTreeMap<String, Preset> =
+            serde_yaml::from_str(&input::read_to_string(presets_path)?)?;
+
+        presets
+            .remove(name)
+            .ok_or_else(|| Error::MissingPreset(name.to_string()))
+    }
+}
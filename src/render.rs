@@ -0,0 +1,37 @@
+use crate::cli::SolveArgs;
+use crate::{planet_labels, sort_factory_solutions};
+use comfy_table::Table;
+use pi2::solver::{PresentableSolutions, Simulation};
+
+/// Renders the simulation's presentable solutions as a table with one row per product.
+pub fn table(simulation: &Simulation, args: &SolveArgs) -> Table {
+    let top = args.top.unwrap_or(usize::MAX);
+    let min_tier = args.factory_min_tier();
+
+    let mut table = Table::new();
+    table.set_header(["Planets", "Tier", "Product"]);
+
+    let rows: Vec<_> = match simulation.presentable_solutions(min_tier) {
+        PresentableSolutions::Factory(solutions) => sort_factory_solutions(solutions, args)
+            .take(top)
+            .map(|(solution, products)| (planet_labels(solution), products))
+            .collect(),
+        PresentableSolutions::PerPlanet(solutions) => solutions
+            .into_iter()
+            .take(top)
+            .map(|(solution, products)| (solution.planet.label.clone(), products))
+            .collect(),
+    };
+
+    for (planets, products) in rows {
+        for product in products {
+            table.add_row([
+                planets.as_str(),
+                &format!("{:?}", product.tier),
+                product.label,
+            ]);
+        }
+    }
+
+    table
+}
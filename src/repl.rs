@@ -0,0 +1,84 @@
+use crate::cli::SolveArgs;
+use crate::{build_solver, load_items, load_system, print_text, require_items};
+use itertools::Itertools;
+use pi2::error;
+use pi2::system::Scan;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::io;
+
+const HELP: &str = "\
+Commands:
+  solve             Run the solver using the options the REPL was started with
+  planet <LABEL>    Show a planet's resources
+  recipe <ITEM_ID>  Show how an item is produced
+  search <QUERY>    Find items whose ID or label contains the query
+  help              Show this message
+  quit              Exit the REPL";
+
+/// Loads the items and system named in `args`, then reads and runs commands against them until
+/// the user quits.
+pub fn run(args: &SolveArgs) -> error::Result<()> {
+    let item_manager = load_items(&args.input.items.items)?;
+    let scan = args.input.scan.as_ref().map(Scan::new).transpose()?;
+    let system = load_system(args, scan.as_ref(), &item_manager)?;
+    require_items(&args.import, &item_manager)?;
+    require_items(&args.exclude_item, &item_manager)?;
+
+    println!("Loaded {system}. Type \"help\" for a list of commands.");
+
+    let mut editor = DefaultEditor::new().map_err(io::Error::other)?;
+
+    loop {
+        let line = match editor.readline("pi> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+            Err(error) => return Err(io::Error::other(error).into()),
+        };
+
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        // History is a convenience, so failing to record an entry shouldn't end the session.
+        let _ = editor.add_history_entry(line);
+
+        let (command, argument) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(command, argument)| (command, argument.trim()));
+
+        match command {
+            "solve" => print_text(&build_solver(args).solve(&system, &item_manager), args),
+            "planet" => match system.planets.iter().find(|p| p.label == argument) {
+                Some(planet) => {
+                    print!("{}", planet.with_extraction_rate(args.base_extraction_rate))
+                }
+                None => println!("No planet labelled '{argument}'"),
+            },
+            "recipe" => match item_manager.get(argument) {
+                Some(item) => println!("{item}"),
+                None => println!("No item with ID '{argument}'"),
+            },
+            "search" => {
+                let query = argument.to_lowercase();
+
+                for item in item_manager
+                    .iter()
+                    .filter(|item| {
+                        item.id.contains(&query) || item.label.to_lowercase().contains(&query)
+                    })
+                    .sorted()
+                {
+                    println!("{} ({})", item.id, item.label);
+                }
+            }
+            "help" => println!("{HELP}"),
+            "quit" | "exit" => break,
+            _ => println!("Unknown command '{command}'. Type \"help\" for a list of commands."),
+        }
+    }
+
+    Ok(())
+}
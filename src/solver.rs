@@ -1,230 +1,3034 @@
-use crate::item::{Item, ItemManager};
-use crate::system::{IterPlanets, Planet};
-use crate::Tier;
-use itertools::Itertools;
-use log::trace;
-use std::collections::HashSet;
-use std::fmt::{Display, Formatter};
-
-#[derive(Debug, Default)]
-pub struct Builder {
-    use_factory_planet: Option<bool>,
-    max_planets: Option<usize>,
-    production_max_tier: Option<Tier>,
-    factory_max_tier: Option<Tier>,
-}
-
-impl Builder {
-    pub fn use_factory_planet<V>(mut self, value: V) -> Self
-    where
-        V: Into<Option<bool>>,
-    {
-        self.use_factory_planet = value.into();
-        self
-    }
-
-    pub fn max_planets<V>(mut self, value: V) -> Self
-    where
-        V: Into<Option<usize>>,
-    {
-        self.max_planets = value.into();
-        self
-    }
-
-    pub fn production_max_tier<V>(mut self, value: V) -> Self
-    where
-        V: Into<Option<Tier>>,
-    {
-        self.production_max_tier = value.into();
-        self
-    }
-
-    pub fn factory_max_tier<V>(mut self, value: V) -> Self
-    where
-        V: Into<Option<Tier>>,
-    {
-        self.factory_max_tier = value.into();
-        self
-    }
-
-    pub fn build(self) -> Solver {
-        let use_factory_planet = self.use_factory_planet.unwrap_or(true);
-
-        let max_planets = self.max_planets.unwrap_or(6);
-        let max_planets = if use_factory_planet {
-            max_planets - 1
-        } else {
-            max_planets
-        };
-
-        let production_max_tier = self.production_max_tier.unwrap_or(if use_factory_planet {
-            Tier::P1
-        } else {
-            Tier::P4
-        });
-
-        Solver {
-            factory_max_tier: self.factory_max_tier.unwrap_or(Tier::P4),
-            production_max_tier,
-            use_factory_planet,
-            max_planets,
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct Solver {
-    production_max_tier: Tier,
-    factory_max_tier: Tier,
-    use_factory_planet: bool,
-    max_planets: usize,
-}
-
-impl Solver {
-    pub fn builder() -> Builder {
-        Builder::default()
-    }
-
-    pub fn solve<'a, P>(&self, planets: &'a P, item_manager: &'a ItemManager) -> Simulation<'a>
-    where
-        P: IterPlanets,
-    {
-        let mut simulation = Simulation::default();
-
-        for planet in planets.iter_planets() {
-            let products = self.solve_cycles(
-                &planet.collect_resources(),
-                item_manager,
-                self.production_max_tier,
-            );
-
-            simulation
-                .planet_solutions
-                .push(Solution { planet, products });
-        }
-
-        if self.use_factory_planet {
-            for planet_set in simulation
-                .planet_solutions
-                .clone()
-                .into_iter()
-                .combinations(self.max_planets)
-            {
-                let inputs = planet_set
-                    .iter()
-                    .flat_map(|solution| &solution.products)
-                    .collect();
-
-                let products = self.solve_cycles(&inputs, item_manager, self.factory_max_tier);
-
-                simulation.factory_solutions.push(FactorySolution {
-                    planets: planet_set,
-                    products,
-                })
-            }
-        }
-
-        simulation
-    }
-
-    fn solve_cycles<'a>(
-        &self,
-        initial_inputs: &HashSet<&Item<'a>>,
-        item_manager: &'a ItemManager,
-        max_tier: Tier,
-    ) -> HashSet<Item<'a>> {
-        let mut products = HashSet::new();
-        let mut next_cycle = self.solve_cycle(initial_inputs, item_manager, max_tier);
-
-        loop {
-            let mut inserted = 0;
-
-            for output in next_cycle.outputs {
-                let is_tier_allowed = output.tier > Tier::R0 && output.tier <= max_tier;
-
-                if is_tier_allowed && products.insert(output) {
-                    inserted += 1;
-                }
-            }
-
-            if inserted == 0 {
-                break;
-            }
-
-            next_cycle = self.solve_cycle(&products.iter().collect(), item_manager, max_tier);
-        }
-
-        products
-    }
-
-    fn solve_cycle<'a>(
-        &self,
-        inputs: &HashSet<&Item<'a>>,
-        item_manager: &'a ItemManager,
-        max_tier: Tier,
-    ) -> Cycle<'a> {
-        let mut cycle = Cycle::default();
-
-        for input in inputs {
-            for product in item_manager.get_products(input).unwrap_or_default() {
-                if product.tier > max_tier {
-                    continue;
-                }
-
-                trace!("Checking if cycle can produce {}", product.id);
-
-                // Unwrap is safe here because an item cannot be returned from
-                // `ItemManager::get_products()` if it has no production information.
-                let production = product.production.as_ref().unwrap();
-
-                if production.can_be_made_using(inputs) {
-                    cycle.outputs.insert(product.clone());
-                    trace!("Cycle can produce {}", product.id);
-                } else {
-                    trace!("Cycle cannot produce {}", product.id);
-                }
-            }
-        }
-
-        cycle
-    }
-}
-
-#[derive(Debug, Default, Eq, PartialEq)]
-struct Cycle<'a> {
-    outputs: HashSet<Item<'a>>,
-}
-
-#[derive(Debug, Default, Clone)]
-pub struct Simulation<'a> {
-    pub planet_solutions: Vec<Solution<'a>>,
-    pub factory_solutions: Vec<FactorySolution<'a>>,
-}
-
-#[derive(Debug, Clone)]
-pub struct Solution<'a> {
-    pub planet: &'a Planet<'a>,
-    pub products: HashSet<Item<'a>>,
-}
-
-impl Display for Solution<'_> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}", &self.planet.label)?;
-
-        for product in self.products.iter().sorted_by_key(|product| product.tier) {
-            if product.tier < Tier::P1 {
-                continue;
-            }
-
-            write!(f, "  {product}")?;
-        }
-
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct FactorySolution<'a> {
-    pub planets: Vec<Solution<'a>>,
-    pub products: HashSet<Item<'a>>,
-}
+use crate::facility::{Budget, Facility, FacilityCost};
+use crate::item::{Item, ItemManager, ProductOrder};
+use crate::system::{IterPlanets, Planet, System};
+use crate::{error, input, Tier};
+use clap::ValueEnum;
+use itertools::Itertools;
+use log::{debug, trace, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::iter;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+#[derive(Debug, Default)]
+pub struct Builder {
+    use_factory_planet: Option<bool>,
+    max_planets: Option<usize>,
+    production_max_tier: Option<Tier>,
+    factory_max_tier: Option<Tier>,
+    max_tier: Option<Tier>,
+    include_planets: Vec<String>,
+    exclude_planets: Vec<String>,
+    max_jumps: Option<u32>,
+    exclude_items: BTreeSet<String>,
+    imports: Vec<String>,
+    import_tier: Option<Tier>,
+    max_cycles: Option<usize>,
+    factory_min_tier: Option<Tier>,
+    factory_planet: Option<String>,
+    require_all_tiers: Option<bool>,
+    min_products: Option<usize>,
+    max_products_per_planet: Option<usize>,
+    show_progress: Option<bool>,
+    import_p4_inputs: Option<bool>,
+    skill_max_tier: Option<Tier>,
+}
+
+impl Builder {
+    pub fn use_factory_planet<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<bool>>,
+    {
+        self.use_factory_planet = value.into();
+        self
+    }
+
+    /// Shorthand for `use_factory_planet(false)`. Only per-planet solutions are solved, and
+    /// production planets default to making products up to P4.
+    pub fn planets_only(self) -> Self {
+        self.use_factory_planet(false)
+    }
+
+    pub fn max_planets<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<usize>>,
+    {
+        self.max_planets = value.into();
+        self
+    }
+
+    pub fn production_max_tier<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<Tier>>,
+    {
+        self.production_max_tier = value.into();
+        self
+    }
+
+    pub fn factory_max_tier<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<Tier>>,
+    {
+        self.factory_max_tier = value.into();
+        self
+    }
+
+    /// Sets both the production and factory max tiers. Either one set explicitly via
+    /// [`Builder::production_max_tier()`] or [`Builder::factory_max_tier()`] takes precedence.
+    pub fn max_tier<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<Tier>>,
+    {
+        self.max_tier = value.into();
+        self
+    }
+
+    /// Drops factory solutions that do not make at least one product of this tier or higher.
+    pub fn factory_min_tier<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<Tier>>,
+    {
+        self.factory_min_tier = value.into();
+        self
+    }
+
+    /// Drops factory solutions that do not make at least one product of every tier from P1 up to
+    /// the factory's max tier, counting the products of the production planets. Off by default.
+    pub fn require_all_tiers<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<bool>>,
+    {
+        self.require_all_tiers = value.into();
+        self
+    }
+
+    /// Drops factory solutions with fewer than this many products at or above the factory's min
+    /// tier. Without a factory planet, planet solutions with fewer than this many products are
+    /// dropped instead. Defaults to 1.
+    pub fn min_products<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<usize>>,
+    {
+        self.min_products = value.into();
+        self
+    }
+
+    /// Limits each production planet to supplying at most this many distinct products to the
+    /// factory, as if its extractor and facility slots were dedicated to just those. Every such
+    /// subset of each planet's products is tried, which can greatly increase the number of factory
+    /// solutions. Unlimited by default.
+    pub fn max_products_per_planet<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<usize>>,
+    {
+        self.max_products_per_planet = value.into();
+        self
+    }
+
+    /// Shows a progress bar on stderr while factory solutions are solved. The bar is only drawn
+    /// when the `progress` feature is enabled and both stdout and stderr are terminals. Off by
+    /// default.
+    pub fn show_progress<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<bool>>,
+    {
+        self.show_progress = value.into();
+        self
+    }
+
+    /// Pins the planet with the given label as the factory planet. It is never used as a
+    /// production planet, so combinations are only chosen from the remaining planets. Its own
+    /// resources, and the P1 items made from them, are added to the factory's inputs.
+    pub fn factory_planet<V>(mut self, label: V) -> Self
+    where
+        V: Into<Option<String>>,
+    {
+        self.factory_planet = label.into();
+        self
+    }
+
+    /// Restricts solving to the planets whose labels contain any of the given filters, ignoring
+    /// case. An empty list includes all planets.
+    pub fn include_planets(mut self, labels: Vec<String>) -> Self {
+        self.include_planets = labels;
+        self
+    }
+
+    /// Skips the planets with the given labels. Exclusions take precedence over inclusions.
+    pub fn exclude_planets(mut self, labels: Vec<String>) -> Self {
+        self.exclude_planets = labels;
+        self
+    }
+
+    /// Excludes planets more than this many jumps from the factory planet's system. See
+    /// [`Planet::jumps_from_factory`].
+    pub fn max_jumps<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<u32>>,
+    {
+        self.max_jumps = value.into();
+        self
+    }
+
+    /// Prevents the solver from producing the items with the given IDs, along with anything that
+    /// requires them as an input.
+    pub fn exclude_items(mut self, ids: Vec<String>) -> Self {
+        self.exclude_items = ids.into_iter().collect();
+        self
+    }
+
+    /// Makes the items with the given IDs available to the factory planet, as if they were bought
+    /// and imported rather than made by a production planet. IDs that aren't in the item manager
+    /// being solved with are ignored, so callers should check them first.
+    pub fn imports(mut self, ids: Vec<String>) -> Self {
+        self.imports = ids;
+        self
+    }
+
+    /// Makes every item of the given tier available to the factory planet, in addition to the
+    /// items given to [`Builder::imports()`]. Items the production planets make are still taken
+    /// from them rather than imported.
+    pub fn import_tier<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<Tier>>,
+    {
+        self.import_tier = value.into();
+        self
+    }
+
+    /// Controls whether imports may include items flagged as P4 inputs, the P1 items that P4
+    /// recipes consume alongside P3 items. When disabled, those imports are ignored, so the factory
+    /// only makes P4 products if its production planets supply the P1 inputs, and otherwise stops
+    /// at P3. Enabled by default.
+    pub fn import_p4_inputs<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<bool>>,
+    {
+        self.import_p4_inputs = value.into();
+        self
+    }
+
+    /// Caps every tier the solver will produce at the highest tier the player has the skills to
+    /// make, regardless of any other max tier. Unlike those options, this is kept on the
+    /// [`Simulation`] so that output can explain which products are blocked by skill level.
+    pub fn skill_max_tier<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<Tier>>,
+    {
+        self.skill_max_tier = value.into();
+        self
+    }
+
+    /// Caps the number of production cycles solved for each set of inputs. Solving normally stops
+    /// once a cycle yields no new products, but a malformed items file can make that take a long
+    /// time. Unlimited by default.
+    pub fn max_cycles<V>(mut self, value: V) -> Self
+    where
+        V: Into<Option<usize>>,
+    {
+        self.max_cycles = value.into();
+        self
+    }
+
+    pub fn build(self) -> Solver {
+        let use_factory_planet = self.use_factory_planet.unwrap_or(true);
+
+        let max_planets = self.max_planets.unwrap_or(6);
+        let max_planets = if use_factory_planet {
+            max_planets - 1
+        } else {
+            max_planets
+        };
+
+        // Production planets only need to feed the factory planet, so they default to P1.
+        // Without a factory planet, each planet must make everything it can on its own.
+        let default_production_max_tier = if use_factory_planet {
+            Tier::P1
+        } else {
+            Tier::P4
+        };
+
+        let production_max_tier = self
+            .production_max_tier
+            .or(self.max_tier)
+            .unwrap_or(default_production_max_tier);
+
+        let factory_max_tier = self.factory_max_tier.or(self.max_tier).unwrap_or(Tier::P4);
+        let factory_min_tier = self.factory_min_tier.unwrap_or(Tier::R0);
+
+        let (production_max_tier, factory_max_tier) = match self.skill_max_tier {
+            Some(skill_max_tier) => {
+                if factory_min_tier > skill_max_tier {
+                    warn!(
+                        "Factory min tier {factory_min_tier:?} is blocked by skill level, which \
+                         only allows up to {skill_max_tier:?}, so no factory solutions will be kept"
+                    );
+                }
+
+                (
+                    production_max_tier.min(skill_max_tier),
+                    factory_max_tier.min(skill_max_tier),
+                )
+            }
+            None => (production_max_tier, factory_max_tier),
+        };
+
+        if use_factory_planet && production_max_tier > factory_max_tier {
+            warn!(
+                "Production planets may make up to {production_max_tier:?}, but the factory only \
+                 makes up to {factory_max_tier:?}, so it cannot use their highest tier products"
+            );
+        }
+
+        Solver {
+            factory_max_tier,
+            factory_min_tier,
+            production_max_tier,
+            use_factory_planet,
+            max_planets,
+            include_planets: self.include_planets,
+            exclude_planets: self.exclude_planets,
+            max_jumps: self.max_jumps,
+            exclude_items: self.exclude_items,
+            imports: self.imports,
+            import_tier: self.import_tier,
+            max_cycles: self.max_cycles.unwrap_or(usize::MAX),
+            factory_planet: self.factory_planet,
+            require_all_tiers: self.require_all_tiers.unwrap_or(false),
+            min_products: self.min_products.unwrap_or(1),
+            max_products_per_planet: self.max_products_per_planet,
+            show_progress: self.show_progress.unwrap_or(false),
+            import_p4_inputs: self.import_p4_inputs.unwrap_or(true),
+            skill_max_tier: self.skill_max_tier,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Solver {
+    production_max_tier: Tier,
+    factory_max_tier: Tier,
+    factory_min_tier: Tier,
+    use_factory_planet: bool,
+    max_planets: usize,
+    include_planets: Vec<String>,
+    exclude_planets: Vec<String>,
+    max_jumps: Option<u32>,
+    exclude_items: BTreeSet<String>,
+    imports: Vec<String>,
+    import_tier: Option<Tier>,
+    max_cycles: usize,
+    factory_planet: Option<String>,
+    require_all_tiers: bool,
+    min_products: usize,
+    max_products_per_planet: Option<usize>,
+    show_progress: bool,
+    import_p4_inputs: bool,
+    skill_max_tier: Option<Tier>,
+}
+
+impl Solver {
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// Returns a SHA-256 hash, in hex, of the items, the planets, and the solver's options. The
+    /// fingerprint only changes when one of those changes, so it can be stored alongside a result
+    /// to tell whether the result is stale.
+    pub fn input_fingerprint<P>(&self, planets: &P, item_manager: &ItemManager) -> String
+    where
+        P: IterPlanets,
+    {
+        let mut hasher = Sha256::new();
+
+        // Neither items nor planets contain anything that fails to serialize, such as maps with
+        // non-string keys.
+        item_manager
+            .hash_into(&mut hasher)
+            .expect("items should serialize");
+
+        for planet in planets.iter_planets() {
+            hasher.update(serde_json::to_vec(planet).expect("planets should serialize"));
+        }
+
+        let tiers = (
+            self.production_max_tier,
+            self.factory_max_tier,
+            self.factory_min_tier,
+            self.skill_max_tier,
+            self.import_tier,
+            self.max_jumps,
+        );
+
+        let options = (
+            self.use_factory_planet,
+            self.max_planets,
+            &self.include_planets,
+            &self.exclude_planets,
+            &self.exclude_items,
+            &self.imports,
+            self.max_cycles,
+            &self.factory_planet,
+            self.require_all_tiers,
+            self.min_products,
+            self.max_products_per_planet,
+            self.import_p4_inputs,
+        );
+
+        hasher.update(format!("{tiers:?}{options:?}"));
+
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Reads a simulation written by [`Simulation::save()`] in place of solving `system` again.
+    /// Fails with [`error::Error::StaleSimulation`] unless it was saved with the same
+    /// [`Solver::input_fingerprint()`] that this solver has for `system` and `item_manager`.
+    pub fn load<'a, P>(
+        &self,
+        path: P,
+        system: &'a System<'a>,
+        item_manager: &'a ItemManager,
+    ) -> error::Result<Simulation<'a>>
+    where
+        P: AsRef<Path>,
+    {
+        let simulation = OwnedSimulation::load(path)?;
+        let fingerprint = self.input_fingerprint(system, item_manager);
+
+        if simulation.input_fingerprint.as_ref() != Some(&fingerprint) {
+            return Err(error::Error::StaleSimulation);
+        }
+
+        simulation.resolve(system, item_manager)
+    }
+
+    /// The highest tier made by each production planet.
+    pub fn production_max_tier(&self) -> Tier {
+        self.production_max_tier
+    }
+
+    /// The highest tier made by the factory planet.
+    pub fn factory_max_tier(&self) -> Tier {
+        self.factory_max_tier
+    }
+
+    /// [`SolutionMode::Factory`] if the factory planet is enabled, or [`SolutionMode::PerPlanet`]
+    /// otherwise.
+    pub fn mode(&self) -> SolutionMode {
+        if self.use_factory_planet {
+            SolutionMode::Factory
+        } else {
+            SolutionMode::PerPlanet
+        }
+    }
+
+    pub fn solve<'a, P>(&self, planets: &'a P, item_manager: &'a ItemManager) -> Simulation<'a>
+    where
+        P: IterPlanets,
+    {
+        let mut factory_solutions = Vec::new();
+
+        let Ok(planet_solutions) = self.solve_streaming(
+            planets,
+            item_manager,
+            |solution| -> Result<(), Infallible> {
+                factory_solutions.push(solution);
+                Ok(())
+            },
+        );
+
+        Simulation {
+            mode: self.mode(),
+            planet_solutions,
+            factory_solutions,
+            skill_max_tier: self.skill_max_tier,
+            input_fingerprint: Some(self.input_fingerprint(planets, item_manager)),
+        }
+    }
+
+    /// Solves in the same way as [`Solver::solve()`], but hands each factory solution to `consumer`
+    /// as soon as it is solved rather than collecting them, which keeps memory use bounded for
+    /// systems with many planet combinations. Returns the per-planet solutions.
+    ///
+    /// Solving stops at the first error returned by `consumer`.
+    pub fn solve_streaming<'a, P, F, E>(
+        &self,
+        planets: &'a P,
+        item_manager: &'a ItemManager,
+        mut consumer: F,
+    ) -> Result<Vec<Solution<'a>>, E>
+    where
+        P: IterPlanets,
+        F: FnMut(FactorySolution<'a>) -> Result<(), E>,
+    {
+        let mut planet_solutions = self.solve_planets(planets, item_manager);
+        self.solve_factories(
+            &planet_solutions,
+            self.max_planets,
+            planets,
+            item_manager,
+            &mut consumer,
+        )?;
+
+        self.retain_planet_solutions(&mut planet_solutions);
+
+        Ok(planet_solutions)
+    }
+
+    /// Solves once for each total planet count in `counts`, as if each count had been given to
+    /// [`Builder::max_planets()`]. Per-planet solutions are only solved once and shared by every
+    /// simulation. Returns each count paired with its simulation.
+    pub fn solve_for_counts<'a, P>(
+        &self,
+        planets: &'a P,
+        item_manager: &'a ItemManager,
+        counts: RangeInclusive<usize>,
+    ) -> Vec<(usize, Simulation<'a>)>
+    where
+        P: IterPlanets,
+    {
+        let planet_solutions = self.solve_planets(planets, item_manager);
+        let input_fingerprint = self.input_fingerprint(planets, item_manager);
+
+        let mut kept_planet_solutions = planet_solutions.clone();
+        self.retain_planet_solutions(&mut kept_planet_solutions);
+
+        counts
+            .map(|count| {
+                let size = if self.use_factory_planet {
+                    count.saturating_sub(1)
+                } else {
+                    count
+                };
+
+                let mut factory_solutions = Vec::new();
+
+                let Ok(()) = self.solve_factories(
+                    &planet_solutions,
+                    size,
+                    planets,
+                    item_manager,
+                    |solution| -> Result<(), Infallible> {
+                        factory_solutions.push(solution);
+                        Ok(())
+                    },
+                );
+
+                let simulation = Simulation {
+                    mode: self.mode(),
+                    planet_solutions: kept_planet_solutions.clone(),
+                    factory_solutions,
+                    skill_max_tier: self.skill_max_tier,
+                    input_fingerprint: Some(input_fingerprint.clone()),
+                };
+
+                (count, simulation)
+            })
+            .collect()
+    }
+
+    /// Solves a factory solution for every combination of `size` production planets, handing each
+    /// one that is kept to `consumer`. Does nothing if the factory planet is disabled.
+    fn solve_factories<'a, P, F, E>(
+        &self,
+        planet_solutions: &[Solution<'a>],
+        size: usize,
+        planets: &'a P,
+        item_manager: &'a ItemManager,
+        mut consumer: F,
+    ) -> Result<(), E>
+    where
+        P: IterPlanets,
+        F: FnMut(FactorySolution<'a>) -> Result<(), E>,
+    {
+        if !self.use_factory_planet {
+            return Ok(());
+        }
+
+        let imports = self.resolve_imports(item_manager);
+        let factory_planet = self.find_factory_planet(planets);
+
+        let candidates: Vec<_> = self.production_candidates(planet_solutions).collect();
+        let progress = Progress::new(
+            self.show_progress,
+            combination_count(candidates.len(), size),
+        );
+
+        for planet_set in candidates.into_iter().combinations(size) {
+            for planet_set in self.dedicated_planet_sets(planet_set) {
+                let solution =
+                    self.solve_factory(planet_set, factory_planet, &imports, item_manager);
+
+                if self.is_factory_solution_kept(&solution) {
+                    consumer(solution)?;
+                }
+            }
+
+            progress.inc();
+        }
+
+        Ok(())
+    }
+
+    /// Returns every product that at least one allowed planet can make entirely on its own, up to
+    /// the factory's max tier. Comparing a factory solution against these shows what the factory
+    /// planet adds; see [`FactorySolution::exclusive_products()`].
+    pub fn standalone_products<'a, P>(
+        &self,
+        planets: &'a P,
+        item_manager: &'a ItemManager,
+    ) -> BTreeSet<Item<'a>>
+    where
+        P: IterPlanets,
+    {
+        planets
+            .iter_planets()
+            .filter(|planet| self.is_planet_allowed(planet))
+            .flat_map(|planet| {
+                self.solve_cycles(
+                    &planet.collect_resources(),
+                    item_manager,
+                    self.factory_max_tier,
+                )
+            })
+            .collect()
+    }
+
+    /// Finds the smallest set of planets able to produce every item in `targets`, either on their
+    /// own or, if the factory planet is enabled, by feeding a factory planet. Sets are tried in
+    /// increasing size, up to every allowed planet, ignoring the configured maximum planet count.
+    /// As with [`Builder::max_planets()`], the factory planet counts towards the size of a set.
+    ///
+    /// Returns `None` if no set of planets can produce all targets, or if any target is not a
+    /// known item.
+    pub fn minimize_planets<'a, P>(
+        &self,
+        targets: &[&str],
+        planets: &'a P,
+        item_manager: &'a ItemManager,
+    ) -> Option<MinimalPlanets<'a>>
+    where
+        P: IterPlanets,
+    {
+        let targets: Vec<_> = targets
+            .iter()
+            .map(|id| item_manager.get(id))
+            .collect::<Option<_>>()?;
+
+        let solutions = self.solve_planets(planets, item_manager);
+        let imports = self.resolve_imports(item_manager);
+        let factory_planet = self.find_factory_planet(planets);
+
+        let covers = |planet_set: &[Solution<'a>], products: Option<&BTreeSet<Item<'a>>>| {
+            targets.iter().all(|target| {
+                products.is_some_and(|products| products.contains(target))
+                    || planet_set
+                        .iter()
+                        .any(|solution| solution.products.contains(target))
+            })
+        };
+
+        // The factory planet counts towards the size of a set, just as it does for
+        // `Builder::max_planets()`, so a factory set holds one fewer production planet.
+        for size in 1..=solutions.len() + 1 {
+            for planet_set in self.production_candidates(&solutions).combinations(size) {
+                if covers(&planet_set, None) {
+                    return Some(MinimalPlanets::PerPlanet(planet_set));
+                }
+            }
+
+            if !self.use_factory_planet || size < 2 {
+                continue;
+            }
+
+            for planet_set in self
+                .production_candidates(&solutions)
+                .combinations(size - 1)
+            {
+                let factory =
+                    self.solve_factory(planet_set, factory_planet, &imports, item_manager);
+
+                if covers(&factory.planets, Some(&factory.products)) {
+                    return Some(MinimalPlanets::Factory(factory));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn solve_planets<'a, P>(
+        &self,
+        planets: &'a P,
+        item_manager: &'a ItemManager,
+    ) -> Vec<Solution<'a>>
+    where
+        P: IterPlanets,
+    {
+        planets
+            .iter_planets()
+            .filter(|planet| self.is_planet_allowed(planet))
+            .map(|planet| {
+                let products = self.solve_cycles(
+                    &planet.collect_resources(),
+                    item_manager,
+                    self.production_max_tier,
+                );
+
+                Solution::new(planet, products)
+            })
+            .collect()
+    }
+
+    /// Returns every way the planets in `planet_set` can each dedicate themselves to at most
+    /// [`Builder::max_products_per_planet()`] of their products. Planets with no more products than
+    /// that keep all of them.
+    fn dedicated_planet_sets<'a>(&self, planet_set: Vec<Solution<'a>>) -> Vec<Vec<Solution<'a>>> {
+        let Some(max_products) = self.max_products_per_planet else {
+            return vec![planet_set];
+        };
+
+        if planet_set.is_empty() {
+            return vec![planet_set];
+        }
+
+        planet_set
+            .into_iter()
+            .map(|solution| {
+                if solution.products.len() <= max_products {
+                    return vec![solution];
+                }
+
+                solution
+                    .products
+                    .iter()
+                    .cloned()
+                    .combinations(max_products)
+                    .map(|products| Solution::new(solution.planet, products.into_iter().collect()))
+                    .collect()
+            })
+            .multi_cartesian_product()
+            .collect()
+    }
+
+    fn solve_factory<'a>(
+        &self,
+        planet_set: Vec<Solution<'a>>,
+        factory_planet: Option<&'a Planet<'a>>,
+        imports: &[Item<'a>],
+        item_manager: &'a ItemManager,
+    ) -> FactorySolution<'a> {
+        let produced = self.factory_production(&planet_set, factory_planet, item_manager);
+
+        let imports: BTreeSet<_> = imports
+            .iter()
+            .filter(|item| !produced.contains(item))
+            .cloned()
+            .collect();
+
+        let inputs = produced.iter().chain(&imports).collect();
+        let products = self.solve_cycles(&inputs, item_manager, self.factory_max_tier);
+
+        FactorySolution::new(planet_set, factory_planet, products, imports)
+    }
+
+    /// Returns every item the factory of `solution` starts from: the products of its production
+    /// planets, anything the factory planet extracts or makes from its own resources, and its
+    /// imports.
+    pub fn factory_inputs<'a>(
+        &self,
+        solution: &FactorySolution<'a>,
+        item_manager: &'a ItemManager,
+    ) -> BTreeSet<Item<'a>> {
+        let mut inputs =
+            self.factory_production(&solution.planets, solution.factory_planet, item_manager);
+
+        inputs.extend(solution.imports.iter().cloned());
+        inputs
+    }
+
+    /// Returns the products of `planet_set`, along with the resources of `factory_planet` and the
+    /// P1 made from them, since a pinned factory planet extracts its own resources too.
+    fn factory_production<'a>(
+        &self,
+        planet_set: &[Solution<'a>],
+        factory_planet: Option<&'a Planet<'a>>,
+        item_manager: &'a ItemManager,
+    ) -> BTreeSet<Item<'a>> {
+        let own_resources = factory_planet
+            .map(|planet| planet.collect_resources())
+            .unwrap_or_default();
+
+        let own_products = self.solve_cycles(
+            &own_resources,
+            item_manager,
+            self.factory_max_tier.min(Tier::P1),
+        );
+
+        planet_set
+            .iter()
+            .flat_map(|solution| solution.products.iter().cloned())
+            .chain(own_products)
+            .chain(own_resources.into_iter().cloned())
+            .collect()
+    }
+
+    /// Drops planet solutions with fewer than [`Builder::min_products()`] products. Planet solutions
+    /// are only presented without a factory planet, and every one of them is needed to solve
+    /// factories, so they are left alone otherwise.
+    fn retain_planet_solutions(&self, solutions: &mut Vec<Solution>) {
+        if !self.use_factory_planet {
+            solutions.retain(|solution| solution.products.len() >= self.min_products);
+        }
+    }
+
+    fn is_factory_solution_kept(&self, solution: &FactorySolution) -> bool {
+        let meets_min_tier = solution
+            .products
+            .iter()
+            .filter(|product| product.tier >= self.factory_min_tier)
+            .count()
+            >= self.min_products.max(1);
+
+        let has_all_tiers = !self.require_all_tiers || {
+            let histogram = solution.tier_histogram();
+
+            [Tier::P1, Tier::P2, Tier::P3, Tier::P4]
+                .into_iter()
+                .filter(|tier| *tier <= self.factory_max_tier)
+                .all(|tier| histogram.contains_key(&tier))
+        };
+
+        meets_min_tier && has_all_tiers
+    }
+
+    /// Returns the planet pinned by [`Builder::factory_planet()`], if it is in `planets`.
+    fn find_factory_planet<'a, P>(&self, planets: &'a P) -> Option<&'a Planet<'a>>
+    where
+        P: IterPlanets,
+    {
+        let label = self.factory_planet.as_ref()?;
+
+        planets.iter_planets().find(|planet| &planet.label == label)
+    }
+
+    /// Returns the solutions whose planets may be used as production planets.
+    fn production_candidates<'s, 'a>(
+        &self,
+        solutions: &'s [Solution<'a>],
+    ) -> impl Iterator<Item = Solution<'a>> + 's
+    where
+        'a: 's,
+    {
+        let factory_planet = self.factory_planet.clone();
+
+        solutions
+            .iter()
+            .filter(move |solution| factory_planet.as_ref() != Some(&solution.planet.label))
+            .cloned()
+    }
+
+    fn resolve_imports<'a>(&self, item_manager: &'a ItemManager) -> Vec<Item<'a>> {
+        let tier_imports = item_manager
+            .iter()
+            .filter(|item| Some(item.tier) == self.import_tier);
+
+        self.imports
+            .iter()
+            .filter_map(|id| item_manager.get(id))
+            .chain(tier_imports)
+            .filter(|item| {
+                let is_allowed = self.import_p4_inputs || !item.is_p4_input;
+
+                if !is_allowed {
+                    debug!("Ignoring import {} as it is a P4 input", item.id);
+                }
+
+                is_allowed
+            })
+            .collect()
+    }
+
+    /// Returns the planet filters given to [`Builder::include_planets()`] that do not match any
+    /// planet in `planets`.
+    pub fn unmatched_include_planets<P>(&self, planets: &P) -> Vec<&str>
+    where
+        P: IterPlanets,
+    {
+        self.include_planets
+            .iter()
+            .filter(|query| {
+                !planets
+                    .iter_planets()
+                    .any(|planet| matches_planet_label(&planet.label, query))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn is_planet_allowed(&self, planet: &Planet) -> bool {
+        let included = self.include_planets.is_empty()
+            || self
+                .include_planets
+                .iter()
+                .any(|query| matches_planet_label(&planet.label, query));
+
+        let within_jumps = self
+            .max_jumps
+            .is_none_or(|max_jumps| planet.jumps_from_factory <= max_jumps);
+
+        included && within_jumps && !self.exclude_planets.contains(&planet.label)
+    }
+
+    fn solve_cycles<'a>(
+        &self,
+        initial_inputs: &BTreeSet<&Item<'a>>,
+        item_manager: &'a ItemManager,
+        max_tier: Tier,
+    ) -> BTreeSet<Item<'a>> {
+        self.solve_cycles_traced(initial_inputs, item_manager, max_tier)
+            .0
+    }
+
+    /// Repeatedly produces everything possible from `initial_inputs` and the products made so far,
+    /// up to `max_tier`, until a production cycle makes nothing new. Returns the final products
+    /// along with each cycle, which shows the order in which products became reachable.
+    ///
+    /// Use [`Solver::production_max_tier()`] or [`Solver::factory_max_tier()`] as `max_tier` to
+    /// retrace a per-planet or factory solution.
+    pub fn solve_cycles_traced<'a>(
+        &self,
+        initial_inputs: &BTreeSet<&Item<'a>>,
+        item_manager: &'a ItemManager,
+        max_tier: Tier,
+    ) -> (BTreeSet<Item<'a>>, Vec<Cycle<'a>>) {
+        let mut products = BTreeSet::new();
+        let mut cycles = Vec::new();
+        let mut next_cycle = self.solve_cycle(initial_inputs, item_manager, max_tier);
+        let mut inputs = initial_inputs.len();
+
+        loop {
+            for output in &next_cycle.outputs {
+                if products.insert(output.clone()) {
+                    next_cycle.added.insert(output.clone());
+                }
+            }
+
+            let is_done = next_cycle.added.is_empty();
+
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                cycle = cycles.len() + 1,
+                inputs,
+                added = ?next_cycle.added.iter().map(|item| item.id).collect::<Vec<_>>(),
+                products = products.len(),
+                "Solved production cycle"
+            );
+
+            #[cfg(not(feature = "tracing"))]
+            trace!(
+                "Production cycle {} considered {inputs} input(s) and added {} product(s)",
+                cycles.len() + 1,
+                next_cycle.added.len()
+            );
+
+            cycles.push(next_cycle);
+
+            if is_done {
+                break;
+            }
+
+            if cycles.len() >= self.max_cycles {
+                warn!(
+                    "Stopped solving after {} production cycle(s) with new products still being \
+                     found",
+                    cycles.len()
+                );
+                break;
+            }
+
+            inputs = products.len();
+            next_cycle = self.solve_cycle(&products.iter().collect(), item_manager, max_tier);
+        }
+
+        (products, cycles)
+    }
+
+    fn solve_cycle<'a>(
+        &self,
+        inputs: &BTreeSet<&Item<'a>>,
+        item_manager: &'a ItemManager,
+        max_tier: Tier,
+    ) -> Cycle<'a> {
+        let mut cycle = Cycle::default();
+
+        for input in inputs {
+            for product in item_manager.get_products(input).unwrap_or_default() {
+                // R0 resources are extracted rather than produced, so they are never products.
+                if product.tier == Tier::R0 || product.tier > max_tier {
+                    continue;
+                }
+
+                if self.exclude_items.contains(product.id) {
+                    trace!("Skipping excluded product {}", product.id);
+                    continue;
+                }
+
+                trace!("Checking if cycle can produce {}", product.id);
+
+                // Unwrap is safe here because an item cannot be returned from
+                // `ItemManager::get_products()` if it has no production information.
+                let production = product.production.as_ref().unwrap();
+
+                if production.can_be_made_using(inputs) {
+                    cycle.outputs.insert(product.clone());
+                    trace!("Cycle can produce {}", product.id);
+                } else {
+                    trace!("Cycle cannot produce {}", product.id);
+                }
+            }
+        }
+
+        cycle
+    }
+}
+
+/// Returns the number of ways to choose `k` items from `n`, saturating at [`u64::MAX`].
+fn combination_count(n: usize, k: usize) -> u64 {
+    if k > n {
+        return 0;
+    }
+
+    let k = k.min(n - k) as u128;
+    let n = n as u128;
+
+    (0..k)
+        .try_fold(1u128, |count, i| {
+            count.checked_mul(n - i).map(|count| count / (i + 1))
+        })
+        .and_then(|count| u64::try_from(count).ok())
+        .unwrap_or(u64::MAX)
+}
+
+/// Counts solved factory combinations on a progress bar, which is cleared when dropped. Does
+/// nothing unless the `progress` feature is enabled and both stdout and stderr are terminals.
+struct Progress {
+    #[cfg(feature = "progress")]
+    bar: Option<indicatif::ProgressBar>,
+}
+
+impl Progress {
+    #[cfg_attr(not(feature = "progress"), allow(unused_variables))]
+    fn new(enabled: bool, total: u64) -> Self {
+        #[cfg(feature = "progress")]
+        {
+            use std::io::{self, IsTerminal};
+
+            let bar = (enabled && io::stdout().is_terminal() && io::stderr().is_terminal())
+                .then(|| indicatif::ProgressBar::new(total));
+
+            Self { bar }
+        }
+
+        #[cfg(not(feature = "progress"))]
+        Self {}
+    }
+
+    fn inc(&self) {
+        #[cfg(feature = "progress")]
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+    }
+}
+
+#[cfg(feature = "progress")]
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// Returns `true` if `query` appears anywhere in `label`, ignoring case. If the query ends in a
+/// Roman numeral, it must also end where a word in the label ends, so that "Tanoo V" does not match
+/// "Tanoo VI".
+fn matches_planet_label(label: &str, query: &str) -> bool {
+    let label = label.to_lowercase();
+    let query = query.to_lowercase();
+
+    let ends_in_numeral = query
+        .rsplit(char::is_whitespace)
+        .next()
+        .is_some_and(is_planet_numeral);
+
+    label.match_indices(&query).any(|(index, _)| {
+        !ends_in_numeral
+            || label[index + query.len()..]
+                .chars()
+                .next()
+                .is_none_or(char::is_whitespace)
+    })
+}
+
+/// Returns `true` if `word` is a lowercase Roman numeral, written the usual way, from 1 to 39.
+/// No system has anywhere near 40 planets, so words such as "mix" aren't mistaken for numerals.
+fn is_planet_numeral(word: &str) -> bool {
+    const NUMERALS: [(usize, &str); 5] = [(10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i")];
+
+    let mut rest = word;
+    let mut value = 0;
+
+    for (numeral_value, numeral) in NUMERALS {
+        while let Some(remainder) = rest.strip_prefix(numeral) {
+            rest = remainder;
+            value += numeral_value;
+        }
+    }
+
+    let mut canonical = String::new();
+    let mut remaining = value;
+
+    for (numeral_value, numeral) in NUMERALS {
+        while remaining >= numeral_value {
+            canonical.push_str(numeral);
+            remaining -= numeral_value;
+        }
+    }
+
+    rest.is_empty() && (1..40).contains(&value) && canonical == word
+}
+
+/// A single production cycle, as returned by [`Solver::solve_cycles_traced()`].
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Cycle<'a> {
+    /// Every product that could be made from the cycle's inputs.
+    pub outputs: BTreeSet<Item<'a>>,
+    /// The outputs that no earlier cycle had made.
+    pub added: BTreeSet<Item<'a>>,
+}
+
+/// The version of the structure that simulations and their solutions are serialized with. It is
+/// bumped whenever that structure changes, so that consumers can tell which structure to expect.
+///
+/// Version 2 added `mode`, `input_fingerprint`, `note`, and the planets' `jumps_from_factory` and
+/// `storage`.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Serializes the wrapped value with an additional `schema_version` field, set to
+/// [`SCHEMA_VERSION`], and an optional `note`, ahead of the value's own fields.
+#[derive(Debug, Serialize)]
+pub struct Versioned<T> {
+    schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(flatten)]
+    value: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            note: None,
+            value,
+        }
+    }
+
+    /// Attaches a free-form note, such as what the output was generated for.
+    pub fn with_note<V>(mut self, note: V) -> Self
+    where
+        V: Into<Option<String>>,
+    {
+        self.note = note.into();
+        self
+    }
+}
+
+/// The result of a solver run.
+///
+/// When serialized, a simulation is a table holding two arrays of tables, along with the optional
+/// `skill_max_tier` and `input_fingerprint`. In TOML, wrapped in [`Versioned`], that looks like
+/// the following (values shortened for brevity). Other formats, such as YAML, share the same
+/// structure.
+///
+/// ```toml
+/// schema_version = 2
+/// note = "Scouted for the corp"  # only with Versioned::with_note()
+/// mode = "factory"  # or "per_planet"
+/// skill_max_tier = "p3"  # only with Builder::skill_max_tier()
+/// input_fingerprint = "b26e91e8..."
+///
+/// [[planet_solutions]]
+/// fits_powergrid = true
+/// max_producible_tier = "p1"
+///
+/// [planet_solutions.planet]
+/// label = "J103326 I"
+/// jumps_from_factory = 2  # omitted when zero
+/// storage = true  # omitted when false
+///
+/// [[planet_solutions.planet.resources]]
+/// density = 0.88
+/// item = { id = "heavy_metals", label = "Heavy Metals", tier = "r0" }
+///
+/// [planet_solutions.planet.budget]
+/// cpu = 25415
+/// powergrid = 19000
+///
+/// [[planet_solutions.products]]
+/// id = "toxic_metals"
+/// label = "Toxic Metals"
+/// tier = "p1"
+///
+/// [[factory_solutions]]
+/// balanced = false
+///
+/// # Each entry has the same shape as an entry in `planet_solutions`.
+/// [[factory_solutions.planets]]
+///
+/// # Only with Builder::factory_planet(); the same shape as `planet_solutions.planet`.
+/// [factory_solutions.factory_planet]
+///
+/// [[factory_solutions.products]]
+/// id = "construction_blocks"
+/// label = "Construction Blocks"
+/// tier = "p2"
+///
+/// # Items the factory imports rather than making, in the same shape as `products`.
+/// [[factory_solutions.imports]]
+///
+/// [[factory_solutions.shortfalls]]
+/// supply = 5
+/// demand = 10
+/// item = { id = "biocells", label = "Biocells", tier = "p2" }
+/// ```
+///
+/// Items are always serialized as their ID, label, and tier; production information is omitted.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Simulation<'a> {
+    /// Whether the solver used a factory planet. See [`Simulation::best_mode()`].
+    pub mode: SolutionMode,
+    pub planet_solutions: Vec<Solution<'a>>,
+    pub factory_solutions: Vec<FactorySolution<'a>>,
+    /// The highest tier the solver was allowed to make due to skill level. See
+    /// [`Builder::skill_max_tier()`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skill_max_tier: Option<Tier>,
+    /// See [`Solver::input_fingerprint()`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_fingerprint: Option<String>,
+}
+
+impl<'a> Simulation<'a> {
+    /// Returns [`SolutionMode::Factory`] if the solver used a factory planet, even if no factory
+    /// solution was kept, or [`SolutionMode::PerPlanet`] otherwise.
+    pub fn best_mode(&self) -> SolutionMode {
+        self.mode
+    }
+
+    /// Returns the solutions for the simulation's [`Simulation::best_mode()`], each paired with the
+    /// products worth showing for it.
+    ///
+    /// Factory solutions only keep products at or above `factory_min_tier`, sorted from highest
+    /// tier to lowest, and are dropped entirely if no products remain. Planet solutions keep all of
+    /// their products, sorted from lowest tier to highest. Solving with the same
+    /// [`Builder::factory_min_tier()`] avoids keeping such solutions in the first place.
+    pub fn presentable_solutions(&self, factory_min_tier: Tier) -> PresentableSolutions<'_, 'a> {
+        match self.best_mode() {
+            SolutionMode::Factory => PresentableSolutions::Factory(
+                self.factory_solutions
+                    .iter()
+                    .map(|solution| {
+                        let products: Vec<_> = solution
+                            .products
+                            .iter()
+                            .filter(|product| product.tier >= factory_min_tier)
+                            .sorted_by_key(|product| Reverse(product.tier))
+                            .collect();
+
+                        (solution, products)
+                    })
+                    .filter(|(_, products)| !products.is_empty())
+                    .collect(),
+            ),
+            SolutionMode::PerPlanet => PresentableSolutions::PerPlanet(
+                self.planet_solutions
+                    .iter()
+                    .map(|solution| {
+                        let products = solution
+                            .products
+                            .iter()
+                            .sorted_by_key(|product| product.tier)
+                            .collect();
+
+                        (solution, products)
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the planet solution that reaches the highest tier, breaking ties by the number of
+    /// products and then by the planet's combined resource density. Returns `None` if there are no
+    /// planet solutions. Factory solutions are not considered.
+    pub fn best_planet(&self) -> Option<&Solution<'a>> {
+        self.planet_solutions
+            .iter()
+            .max_by(|a, b| a.compare_reach(b))
+    }
+
+    /// Drops every planet solution except the one returned by [`Simulation::best_planet()`].
+    pub fn retain_best_planet(&mut self) {
+        self.planet_solutions = self.best_planet().cloned().into_iter().collect();
+    }
+
+    /// Returns how many factory solutions each production planet appears in, paired with its
+    /// label. Sorted from most to least frequent, then by label.
+    pub fn planet_frequency(&self) -> Vec<(String, usize)> {
+        self.factory_solutions
+            .iter()
+            .flat_map(|solution| &solution.planets)
+            .counts_by(|solution| &solution.planet.label)
+            .into_iter()
+            .map(|(label, count)| (label.clone(), count))
+            .sorted_by(|(a_label, a), (b_label, b)| b.cmp(a).then_with(|| a_label.cmp(b_label)))
+            .collect()
+    }
+
+    /// Returns the labels of the planets whose products are never used as an input to a product
+    /// of any factory solution, sorted by label. Planets that don't appear in any factory solution
+    /// are included.
+    pub fn non_contributing_planets(&self) -> Vec<&str> {
+        let contributing: BTreeSet<&str> = self
+            .factory_solutions
+            .iter()
+            .flat_map(|solution| {
+                let inputs: BTreeSet<&Item> = solution
+                    .products
+                    .iter()
+                    .flat_map(|product| &product.production)
+                    .flat_map(|production| &production.inputs)
+                    .map(|input| &input.item)
+                    .collect();
+
+                solution
+                    .planets
+                    .iter()
+                    .filter(move |planet| planet.products.iter().any(|p| inputs.contains(p)))
+                    .map(|planet| planet.planet.label.as_str())
+            })
+            .collect();
+
+        self.planet_solutions
+            .iter()
+            .map(|solution| solution.planet.label.as_str())
+            .filter(|label| !contributing.contains(label))
+            .sorted()
+            .collect()
+    }
+
+    /// Returns each resource that can be extracted on more than one planet, paired with the labels
+    /// of those planets. Sorted by resource label.
+    pub fn resource_overlap(&self) -> Vec<(String, Vec<&str>)> {
+        planets_by_resource(self.planet_solutions.iter().map(|solution| solution.planet))
+            .into_iter()
+            .filter(|(_, planets)| planets.len() > 1)
+            .map(|(resource, planets)| (resource.to_string(), planets))
+            .collect()
+    }
+
+    /// Returns `true` if items of `tier` could not be made because of the skill level given to
+    /// [`Builder::skill_max_tier()`].
+    pub fn is_blocked_by_skill(&self, tier: Tier) -> bool {
+        self.skill_max_tier
+            .is_some_and(|skill_max_tier| tier > skill_max_tier)
+    }
+
+    /// Returns the IDs of every item with production information that does not appear as a product
+    /// in any solution, sorted by ID.
+    pub fn unreachable_products<'m>(&self, item_manager: &'m ItemManager) -> Vec<&'m str> {
+        let reachable = self.reachable_products();
+
+        item_manager
+            .iter()
+            .filter(|item| item.production.is_some() && !reachable.contains(item.id))
+            .map(|item| item.id)
+            .sorted()
+            .collect()
+    }
+
+    /// Returns how many items of `tier` appear as a product in any solution, paired with how many
+    /// items of `tier` the catalog defines.
+    pub fn tier_coverage(&self, tier: Tier, item_manager: &ItemManager) -> (usize, usize) {
+        let reachable = self.reachable_products();
+        let items: Vec<_> = item_manager
+            .iter()
+            .filter(|item| item.tier == tier)
+            .collect();
+
+        let reached = items
+            .iter()
+            .filter(|item| reachable.contains(item.id))
+            .count();
+
+        (reached, items.len())
+    }
+
+    /// Returns the fraction, from 0 to 1, of the catalog's P4 items that appear as a product in any
+    /// solution. See [`Simulation::tier_coverage()`].
+    pub fn p4_coverage(&self, item_manager: &ItemManager) -> f32 {
+        match self.tier_coverage(Tier::P4, item_manager) {
+            (_, 0) => 0.0,
+            (reached, total) => reached as f32 / total as f32,
+        }
+    }
+
+    /// Returns every recipe for an item of `tier` that no solution can make, paired with the inputs
+    /// it is missing. Inputs count as available if any solution makes them or any planet in the
+    /// simulation extracts them. Sorted by item.
+    pub fn blocked_recipes<'s>(
+        &'s self,
+        tier: Tier,
+        item_manager: &'s ItemManager,
+    ) -> Vec<(Item<'s>, Vec<Item<'s>>)>
+    where
+        'a: 's,
+    {
+        let reachable = self.reachable_products();
+        let available: BTreeSet<&Item<'s>> = self
+            .planet_solutions
+            .iter()
+            .flat_map(|solution| solution.products.iter().chain(solution.resources()))
+            .chain(
+                self.factory_solutions
+                    .iter()
+                    .flat_map(|solution| &solution.products),
+            )
+            .collect();
+
+        item_manager
+            .iter()
+            .filter(|item| item.tier == tier && !reachable.contains(item.id))
+            .filter_map(|item| {
+                let missing: Vec<Item> = item
+                    .production
+                    .as_ref()?
+                    .missing_inputs(&available)
+                    .into_iter()
+                    .cloned()
+                    .collect();
+
+                Some((item, missing))
+            })
+            .collect()
+    }
+
+    /// Returns the highest tier of any product made by any solution, or `None` if nothing can be
+    /// made.
+    pub fn max_tier(&self) -> Option<Tier> {
+        self.planet_solutions
+            .iter()
+            .filter_map(|solution| solution.max_producible_tier())
+            .chain(
+                self.factory_solutions
+                    .iter()
+                    .flat_map(|solution| &solution.products)
+                    .map(|product| product.tier),
+            )
+            .max()
+    }
+
+    /// Returns the IDs of every product made by any solution.
+    fn reachable_products(&self) -> BTreeSet<&str> {
+        self.planet_solutions
+            .iter()
+            .flat_map(|solution| &solution.products)
+            .chain(
+                self.factory_solutions
+                    .iter()
+                    .flat_map(|solution| &solution.products),
+            )
+            .map(|product| product.id)
+            .collect()
+    }
+
+    /// Compares this simulation against `other`, reporting the products each solution gains or
+    /// loses in `other`. Solutions are matched by the labels of their planets, so a solution that
+    /// only exists in one simulation gains or loses all of its products. Solutions whose products
+    /// are unchanged are omitted.
+    pub fn diff<'s>(&'s self, other: &'s Simulation<'s>) -> SimulationDiff<'s> {
+        type Products<'s> = Option<&'s BTreeSet<Item<'s>>>;
+
+        let mut matched: BTreeMap<Vec<&str>, (Products, Products)> = BTreeMap::new();
+
+        for (simulation, is_other) in [(self as &Simulation<'s>, false), (other, true)] {
+            let planets = simulation
+                .planet_solutions
+                .iter()
+                .map(|solution| (vec![solution.planet.label.as_str()], &solution.products));
+
+            let factories = simulation.factory_solutions.iter().map(|solution| {
+                let labels = solution
+                    .planets
+                    .iter()
+                    .map(|planet| planet.planet.label.as_str())
+                    .sorted()
+                    .collect();
+
+                (labels, &solution.products)
+            });
+
+            for (labels, products) in planets.chain(factories) {
+                let entry = matched.entry(labels).or_default();
+
+                if is_other {
+                    entry.1 = Some(products);
+                } else {
+                    entry.0 = Some(products);
+                }
+            }
+        }
+
+        let solutions = matched
+            .into_iter()
+            .filter_map(|(planets, (before, after))| {
+                let before = before.into_iter().flatten();
+                let after = after.into_iter().flatten();
+
+                let before_set: BTreeSet<_> = before.clone().collect();
+                let after_set: BTreeSet<_> = after.clone().collect();
+
+                let diff = SolutionDiff {
+                    planets,
+                    added: after
+                        .filter(|product| !before_set.contains(product))
+                        .sorted_by_key(|product| (product.tier, product.label))
+                        .collect(),
+                    removed: before
+                        .filter(|product| !after_set.contains(product))
+                        .sorted_by_key(|product| (product.tier, product.label))
+                        .collect(),
+                };
+
+                (!diff.added.is_empty() || !diff.removed.is_empty()).then_some(diff)
+            })
+            .collect();
+
+        SimulationDiff { solutions }
+    }
+
+    /// Writes the simulation to `path` as JSON, in the shape of an [`OwnedSimulation`].
+    pub fn save<P>(&self, path: P) -> error::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        OwnedSimulation::from(self).save(path)
+    }
+
+    /// Reads a simulation written by [`Simulation::save()`], resolving its planets against
+    /// `system` and its items against `item_manager`.
+    ///
+    /// The simulation is not solved again, so it reflects the inputs it was saved with. Compare its
+    /// [`Simulation::input_fingerprint`] against [`Solver::input_fingerprint()`] to tell whether
+    /// it is stale, or load it with [`Solver::load()`] to fail if it is.
+    pub fn load<P>(
+        path: P,
+        system: &'a System<'a>,
+        item_manager: &'a ItemManager,
+    ) -> error::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        OwnedSimulation::load(path)?.resolve(system, item_manager)
+    }
+}
+
+/// A [`Simulation`] that owns its data, so that it can be deserialized and kept around without the
+/// [`System`] and [`ItemManager`] it was solved from.
+///
+/// A [`Simulation`] borrows its planets and items, which is what lets it be built cheaply while
+/// solving, but means it can only live as long as the system and item manager do. This type keeps
+/// planets by label and items by ID instead; call [`OwnedSimulation::resolve()`] to turn it back
+/// into a borrowed [`Simulation`]. Anything that can be derived from the planets and items, such
+/// as whether a solution fits a planet's powergrid, is recomputed while resolving.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct OwnedSimulation {
+    #[serde(default)]
+    pub mode: SolutionMode,
+    pub planet_solutions: Vec<OwnedSolution>,
+    pub factory_solutions: Vec<OwnedFactorySolution>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skill_max_tier: Option<Tier>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_fingerprint: Option<String>,
+}
+
+/// An owned [`Solution`]. See [`OwnedSimulation`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnedSolution {
+    /// The planet's label.
+    pub planet: String,
+    /// The IDs of the solution's products.
+    pub products: Vec<String>,
+}
+
+/// An owned [`FactorySolution`]. See [`OwnedSimulation`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnedFactorySolution {
+    pub planets: Vec<OwnedSolution>,
+    /// The label of the factory planet, if one was given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub factory_planet: Option<String>,
+    /// The IDs of the solution's products.
+    pub products: Vec<String>,
+    /// The IDs of the solution's imports.
+    pub imports: Vec<String>,
+}
+
+impl OwnedSimulation {
+    /// Reads a simulation from the JSON file at `path`, or stdin if the path is
+    /// [`crate::input::STDIN_PATH`].
+    pub fn load<P>(path: P) -> error::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let contents = input::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(error::Error::JsonDeserialize)
+    }
+
+    /// Writes the simulation to `path` as JSON.
+    pub fn save<P>(&self, path: P) -> error::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Borrows planets from `system` and items from `item_manager` to rebuild the [`Simulation`].
+    /// Fails if any planet label or item ID can't be found.
+    pub fn resolve<'a>(
+        &self,
+        system: &'a System<'a>,
+        item_manager: &'a ItemManager,
+    ) -> error::Result<Simulation<'a>> {
+        let planet_solutions = self
+            .planet_solutions
+            .iter()
+            .map(|solution| solution.resolve(system, item_manager))
+            .collect::<error::Result<_>>()?;
+
+        let factory_solutions = self
+            .factory_solutions
+            .iter()
+            .map(|solution| solution.resolve(system, item_manager))
+            .collect::<error::Result<_>>()?;
+
+        Ok(Simulation {
+            mode: self.mode,
+            planet_solutions,
+            factory_solutions,
+            skill_max_tier: self.skill_max_tier,
+            input_fingerprint: self.input_fingerprint.clone(),
+        })
+    }
+}
+
+impl From<&Simulation<'_>> for OwnedSimulation {
+    fn from(simulation: &Simulation) -> Self {
+        Self {
+            mode: simulation.mode,
+            planet_solutions: simulation
+                .planet_solutions
+                .iter()
+                .map(OwnedSolution::from)
+                .collect(),
+            factory_solutions: simulation
+                .factory_solutions
+                .iter()
+                .map(OwnedFactorySolution::from)
+                .collect(),
+            skill_max_tier: simulation.skill_max_tier,
+            input_fingerprint: simulation.input_fingerprint.clone(),
+        }
+    }
+}
+
+impl OwnedSolution {
+    fn resolve<'a>(
+        &self,
+        system: &'a System<'a>,
+        item_manager: &'a ItemManager,
+    ) -> error::Result<Solution<'a>> {
+        Ok(Solution::new(
+            resolve_planet(system, &self.planet)?,
+            resolve_items(item_manager, &self.products)?,
+        ))
+    }
+}
+
+impl From<&Solution<'_>> for OwnedSolution {
+    fn from(solution: &Solution) -> Self {
+        Self {
+            planet: solution.planet.label.clone(),
+            products: owned_ids(&solution.products),
+        }
+    }
+}
+
+impl OwnedFactorySolution {
+    fn resolve<'a>(
+        &self,
+        system: &'a System<'a>,
+        item_manager: &'a ItemManager,
+    ) -> error::Result<FactorySolution<'a>> {
+        let planets: Vec<_> = self
+            .planets
+            .iter()
+            .map(|solution| solution.resolve(system, item_manager))
+            .collect::<error::Result<_>>()?;
+
+        let factory_planet = self
+            .factory_planet
+            .as_deref()
+            .map(|label| resolve_planet(system, label))
+            .transpose()?;
+
+        let products = resolve_items(item_manager, &self.products)?;
+        let imports = resolve_items(item_manager, &self.imports)?;
+        let shortfalls = find_shortfalls(&planets, &products, &imports);
+
+        Ok(FactorySolution {
+            planets,
+            factory_planet,
+            products,
+            imports,
+            balanced: shortfalls.is_empty(),
+            shortfalls,
+        })
+    }
+}
+
+impl From<&FactorySolution<'_>> for OwnedFactorySolution {
+    fn from(solution: &FactorySolution) -> Self {
+        Self {
+            planets: solution.planets.iter().map(OwnedSolution::from).collect(),
+            factory_planet: solution.factory_planet.map(|planet| planet.label.clone()),
+            products: owned_ids(&solution.products),
+            imports: owned_ids(&solution.imports),
+        }
+    }
+}
+
+fn owned_ids(items: &BTreeSet<Item>) -> Vec<String> {
+    items.iter().map(|item| item.id.to_owned()).collect()
+}
+
+fn resolve_planet<'a>(system: &'a System<'a>, label: &str) -> error::Result<&'a Planet<'a>> {
+    system
+        .planets
+        .iter()
+        .find(|planet| planet.label == label)
+        .ok_or_else(|| error::Error::MissingPlanet(label.to_owned()))
+}
+
+fn resolve_items<'a>(
+    item_manager: &'a ItemManager,
+    ids: &[String],
+) -> error::Result<BTreeSet<Item<'a>>> {
+    ids.iter()
+        .map(|id| {
+            item_manager
+                .get(id)
+                .ok_or_else(|| error::Error::create_missing_item(id))
+        })
+        .collect()
+}
+
+/// The result of [`Simulation::diff()`].
+#[derive(Debug, Default, Clone)]
+pub struct SimulationDiff<'s> {
+    pub solutions: Vec<SolutionDiff<'s>>,
+}
+
+impl SimulationDiff<'_> {
+    /// Returns `true` if no solution gained or lost any products.
+    pub fn is_empty(&self) -> bool {
+        self.solutions.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SolutionDiff<'s> {
+    /// The sorted labels of the planets in the solution.
+    pub planets: Vec<&'s str>,
+    /// Products only made by the solution in the other simulation.
+    pub added: Vec<&'s Item<'s>>,
+    /// Products only made by the solution in this simulation.
+    pub removed: Vec<&'s Item<'s>>,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SolutionMode {
+    /// Production planets feed a factory planet.
+    Factory,
+    /// Each planet produces on its own.
+    #[default]
+    PerPlanet,
+}
+
+/// The result of [`Solver::minimize_planets()`].
+#[derive(Debug)]
+pub enum MinimalPlanets<'a> {
+    /// The planets produce every target on their own.
+    PerPlanet(Vec<Solution<'a>>),
+    /// The planets feed a factory planet, which produces the remaining targets.
+    Factory(FactorySolution<'a>),
+}
+
+impl MinimalPlanets<'_> {
+    /// Returns the number of planets used, counting the factory planet.
+    pub fn planet_count(&self) -> usize {
+        match self {
+            Self::PerPlanet(solutions) => solutions.len(),
+            Self::Factory(solution) => solution.planets.len() + 1,
+        }
+    }
+}
+
+/// See [`Simulation::presentable_solutions()`].
+#[derive(Debug)]
+pub enum PresentableSolutions<'s, 'a> {
+    Factory(Vec<(&'s FactorySolution<'a>, Vec<&'s Item<'a>>)>),
+    PerPlanet(Vec<(&'s Solution<'a>, Vec<&'s Item<'a>>)>),
+}
+
+impl PresentableSolutions<'_, '_> {
+    /// Returns `true` if there are no solutions with products to present.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Factory(solutions) => solutions.is_empty(),
+            Self::PerPlanet(solutions) => solutions.iter().all(|(_, products)| products.is_empty()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Solution<'a> {
+    pub planet: &'a Planet<'a>,
+    pub products: BTreeSet<Item<'a>>,
+    /// `true` if the planet's command center can power the facilities the solution needs.
+    pub fits_powergrid: bool,
+    /// Serialized alongside the products, so that consumers don't need to scan them.
+    max_producible_tier: Option<Tier>,
+}
+
+impl<'a> Solution<'a> {
+    fn new(planet: &'a Planet<'a>, products: BTreeSet<Item<'a>>) -> Self {
+        let mut solution = Self {
+            planet,
+            max_producible_tier: products.iter().map(|product| product.tier).max(),
+            products,
+            fits_powergrid: true,
+        };
+
+        solution.fits_powergrid = planet.budget.covers(solution.facility_usage());
+        solution
+    }
+
+    /// Returns the highest tier of the solution's products, or `None` if it has no products.
+    pub fn max_producible_tier(&self) -> Option<Tier> {
+        self.max_producible_tier
+    }
+
+    /// Compares two solutions by the highest tier they reach, then by their number of products,
+    /// and then by the planet's combined resource density. The greater solution reaches further.
+    pub fn compare_reach(&self, other: &Self) -> Ordering {
+        let density = |solution: &Self| -> f32 {
+            solution
+                .planet
+                .resources
+                .iter()
+                .map(|resource| resource.density)
+                .sum()
+        };
+
+        self.max_producible_tier()
+            .cmp(&other.max_producible_tier())
+            .then_with(|| self.products.len().cmp(&other.products.len()))
+            .then_with(|| density(self).total_cmp(&density(other)))
+    }
+
+    /// Estimates the CPU and powergrid needed to run the solution, assuming one extractor for each
+    /// resource its products rely on and one facility for each product.
+    pub fn facility_usage(&self) -> Budget {
+        self.facilities().map(|f| f.usage()).sum()
+    }
+
+    /// Returns the number of facilities, CPU, powergrid, and ISK needed to set up the planet, using
+    /// the same facilities as [`Solution::facility_usage()`].
+    pub fn facility_cost(&self) -> FacilityCost {
+        FacilityCost::of(self.facilities())
+    }
+
+    /// Returns one extractor for each distinct resource the products need, and one producing
+    /// facility for each product.
+    fn facilities(&self) -> impl Iterator<Item = Facility> + '_ {
+        let extractors = self
+            .products
+            .iter()
+            .flat_map(|product| product.root_resources())
+            .collect::<BTreeSet<_>>()
+            .len();
+
+        let extractors = iter::repeat_n(Facility::Extractor, extractors);
+        let producers = self
+            .products
+            .iter()
+            .filter_map(|product| Facility::for_tier(product.tier));
+
+        extractors.chain(producers)
+    }
+
+    /// Returns the resources extracted on this solution's planet.
+    pub fn resources(&self) -> impl Iterator<Item = &Item<'a>> {
+        self.planet.collect_resources().into_iter()
+    }
+
+    /// Returns the resources `product` is made from that the planet extracts at a density below
+    /// `threshold`, sorted by ID.
+    pub fn marginal_resources<'s>(
+        &'s self,
+        product: &'s Item<'a>,
+        threshold: f32,
+    ) -> Vec<&'s Item<'a>> {
+        product
+            .root_resources()
+            .into_iter()
+            .filter(|item| {
+                self.planet
+                    .resources
+                    .iter()
+                    .any(|resource| resource.item == **item && resource.density < threshold)
+            })
+            .collect()
+    }
+
+    /// Roughly estimates, by product ID, how many facilities each product would need in order to
+    /// consume everything the planet extracts, counting the facilities for intermediate products
+    /// as well. Each product is estimated as if it were the only one drawing on the planet's
+    /// resources.
+    ///
+    /// Extraction is estimated using `base_rate` (see [`Resource::estimated_rate()`]). Facilities
+    /// are assumed to complete a cycle every `cycle_hours`, or every 30 minutes for P1 and every
+    /// hour for everything else if not provided.
+    ///
+    /// [`Resource::estimated_rate()`]: crate::system::Resource::estimated_rate
+    pub fn facility_estimate(
+        &self,
+        base_rate: u32,
+        cycle_hours: Option<f64>,
+    ) -> HashMap<&str, u32> {
+        self.products
+            .iter()
+            .map(|product| {
+                let (_, facilities) = estimate_chain(product, self.planet, base_rate, cycle_hours);
+
+                (product.id, facilities.ceil() as u32)
+            })
+            .collect()
+    }
+}
+
+/// Returns the units of `item` the planet could make per hour, and the (fractional) number of
+/// facilities needed across its production chain to do so.
+fn estimate_chain(
+    item: &Item,
+    planet: &Planet,
+    base_rate: u32,
+    cycle_hours: Option<f64>,
+) -> (f64, f64) {
+    let Some(production) = &item.production else {
+        let extracted = planet
+            .resources
+            .iter()
+            .find(|resource| resource.item == *item)
+            .map_or(0, |resource| resource.estimated_rate(base_rate));
+
+        return (f64::from(extracted), 0.0);
+    };
+
+    let hours = cycle_hours.unwrap_or_else(|| 1.0 / f64::from(cycles_per_hour(item.tier)));
+    let mut facilities = f64::INFINITY;
+    let mut upstream = 0.0;
+
+    for input in &production.inputs {
+        let (supply, input_facilities) =
+            estimate_chain(&input.item, planet, base_rate, cycle_hours);
+        let demand = f64::from(input.amount) / hours;
+
+        facilities = facilities.min(supply / demand);
+        upstream += input_facilities;
+    }
+
+    if !facilities.is_finite() {
+        return (0.0, 0.0);
+    }
+
+    let output = facilities * f64::from(production.quantity) / hours;
+
+    (output, facilities + upstream)
+}
+
+/// Writes the planet label followed by each product. Using the alternate flag (`{:#}`) also
+/// includes the resources extracted on the planet.
+impl Display for Solution<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", &self.planet.label)?;
+
+        let resources = f
+            .alternate()
+            .then(|| self.resources())
+            .into_iter()
+            .flatten();
+
+        for product in self
+            .products
+            .iter()
+            .chain(resources)
+            .sorted_by(|a, b| ProductOrder::Tier.compare(a, b))
+        {
+            writeln!(f, "  {product}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FactorySolution<'a> {
+    pub planets: Vec<Solution<'a>>,
+    /// The planet pinned as the factory, if one was given.
+    pub factory_planet: Option<&'a Planet<'a>>,
+    pub products: BTreeSet<Item<'a>>,
+    /// Imported items that none of the production planets make.
+    pub imports: BTreeSet<Item<'a>>,
+    /// `true` if the solution has no [`Shortfall`]s.
+    pub balanced: bool,
+    pub shortfalls: Vec<Shortfall<'a>>,
+}
+
+impl<'a> FactorySolution<'a> {
+    /// Returns the estimated ISK per hour of the solution's products, assuming one facility is
+    /// producing each of them. Items without a value do not contribute to the total.
+    pub fn estimated_value(&self) -> f64 {
+        self.products
+            .iter()
+            .map(|product| f64::from(hourly_output(product)) * product.value)
+            .sum()
+    }
+
+    /// Returns the total setup cost of the production planets, plus one facility on the factory
+    /// planet for each product that no production planet makes.
+    pub fn facility_cost(&self) -> FacilityCost {
+        let produced: BTreeSet<_> = self
+            .planets
+            .iter()
+            .flat_map(|solution| &solution.products)
+            .collect();
+
+        let factory = FacilityCost::of(
+            self.products
+                .iter()
+                .filter(|product| !produced.contains(product))
+                .filter_map(|product| Facility::for_tier(product.tier)),
+        );
+
+        self.planets
+            .iter()
+            .map(|solution| solution.facility_cost())
+            .sum::<FacilityCost>()
+            + factory
+    }
+
+    fn new(
+        planets: Vec<Solution<'a>>,
+        factory_planet: Option<&'a Planet<'a>>,
+        products: BTreeSet<Item<'a>>,
+        imports: BTreeSet<Item<'a>>,
+    ) -> Self {
+        let shortfalls = find_shortfalls(&planets, &products, &imports);
+
+        Self {
+            planets,
+            factory_planet,
+            products,
+            imports,
+            balanced: shortfalls.is_empty(),
+            shortfalls,
+        }
+    }
+
+    /// Returns the distinct resources extracted across all planets in this solution.
+    pub fn resources(&self) -> BTreeSet<&Item<'a>> {
+        self.planets
+            .iter()
+            .flat_map(|solution| solution.resources())
+            .collect()
+    }
+
+    /// Returns `true` if `product` cannot be made without at least one of the solution's imports.
+    pub fn depends_on_imports(&self, product: &Item) -> bool {
+        self.imports.iter().any(|import| product.requires(import))
+    }
+
+    /// Returns the imports that at least one of the solution's products requires, which are the
+    /// ones that actually need to be bought.
+    pub fn required_imports(&self) -> BTreeSet<&Item<'a>> {
+        self.imports
+            .iter()
+            .filter(|import| self.products.iter().any(|product| product.requires(import)))
+            .collect()
+    }
+
+    /// Returns the resources `product` is made from whose highest density on any of the production
+    /// planets is below `threshold`, sorted by ID. Resources no production planet extracts, such as
+    /// those behind imports, are ignored.
+    pub fn marginal_resources<'s>(
+        &'s self,
+        product: &'s Item<'a>,
+        threshold: f32,
+    ) -> Vec<&'s Item<'a>> {
+        product
+            .root_resources()
+            .into_iter()
+            .filter(|item| {
+                self.planets
+                    .iter()
+                    .flat_map(|solution| &solution.planet.resources)
+                    .filter(|resource| resource.item == **item)
+                    .map(|resource| resource.density)
+                    .max_by(f32::total_cmp)
+                    .is_some_and(|density| density < threshold)
+            })
+            .collect()
+    }
+
+    /// Returns the total jumps from the factory planet's system to each production planet, which
+    /// approximates the cost of hauling the factory's inputs.
+    pub fn total_jumps(&self) -> u32 {
+        self.planets
+            .iter()
+            .map(|solution| solution.planet.jumps_from_factory)
+            .sum()
+    }
+
+    /// Returns the combined density of every resource on the production planets.
+    pub fn total_density(&self) -> f32 {
+        self.planets
+            .iter()
+            .flat_map(|solution| &solution.planet.resources)
+            .map(|resource| resource.density)
+            .sum()
+    }
+
+    /// Compares two solutions by their [`FactorySolution::tier_histogram()`], so that the one with
+    /// more products at the highest tier where they differ comes first. Solutions with identical
+    /// histograms are ordered by each of `tiebreaks` in turn.
+    pub fn rank(&self, other: &Self, tiebreaks: &[Tiebreak]) -> Ordering {
+        let (ours, theirs) = (self.tier_histogram(), other.tier_histogram());
+        let by_histogram = Tier::value_variants()
+            .iter()
+            .rev()
+            .map(|tier| {
+                let count = |histogram: &BTreeMap<Tier, usize>| histogram.get(tier).copied();
+                count(&theirs).cmp(&count(&ours))
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal);
+
+        tiebreaks.iter().fold(by_histogram, |ordering, tiebreak| {
+            ordering.then_with(|| tiebreak.compare(self, other))
+        })
+    }
+
+    /// Returns each product that more than one production planet makes, paired with the labels of
+    /// those planets. Sorted by product.
+    pub fn production_overlaps(&self) -> Vec<(&Item<'a>, Vec<&str>)> {
+        let mut makers: BTreeMap<&Item<'a>, Vec<&str>> = BTreeMap::new();
+
+        for solution in &self.planets {
+            for product in &solution.products {
+                makers
+                    .entry(product)
+                    .or_default()
+                    .push(solution.planet.label.as_str());
+            }
+        }
+
+        makers
+            .into_iter()
+            .filter(|(_, planets)| planets.len() > 1)
+            .collect()
+    }
+
+    /// Suggests a single production planet for each product in
+    /// [`FactorySolution::production_overlaps()`], so that no product is made twice. Products with
+    /// the fewest candidates are assigned first, each to the candidate with the fewest products
+    /// assigned so far, counting those only it makes.
+    pub fn suggested_assignment(&self) -> Vec<(&Item<'a>, &str)> {
+        let overlaps = self.production_overlaps();
+        let mut load: HashMap<&str, usize> = self
+            .planets
+            .iter()
+            .map(|solution| {
+                let unique = solution
+                    .products
+                    .iter()
+                    .filter(|product| !overlaps.iter().any(|(item, _)| item == product))
+                    .count();
+
+                (solution.planet.label.as_str(), unique)
+            })
+            .collect();
+
+        overlaps
+            .iter()
+            .sorted_by_key(|(_, planets)| planets.len())
+            .map(|(product, planets)| {
+                let planet = *planets
+                    .iter()
+                    .min_by_key(|planet| (load.get(*planet).copied().unwrap_or(0), **planet))
+                    .expect("overlapping products are made on more than one planet");
+
+                *load.entry(planet).or_default() += 1;
+
+                (*product, planet)
+            })
+            .sorted_by_key(|(product, _)| *product)
+            .collect()
+    }
+
+    /// Returns production planets whose every product is also made by another planet in the
+    /// solution, chosen so that removing all of them together leaves the factory's inputs, and
+    /// therefore its products, unchanged. Planets are considered in order, and each one is only
+    /// chosen if the planets that remain still supply all of its products.
+    pub fn redundant_planets(&self) -> Vec<&Solution<'a>> {
+        let mut removed = vec![false; self.planets.len()];
+
+        for (index, solution) in self.planets.iter().enumerate() {
+            removed[index] = solution.products.iter().all(|product| {
+                self.planets.iter().enumerate().any(|(other, s)| {
+                    other != index && !removed[other] && s.products.contains(product)
+                })
+            });
+        }
+
+        self.planets
+            .iter()
+            .zip(removed)
+            .filter(|(_, removed)| *removed)
+            .map(|(solution, _)| solution)
+            .collect()
+    }
+
+    /// Returns the distinct resources the production planets in this solution must extract in
+    /// order to make all of their products, along with any the factory planet extracts for its
+    /// own products.
+    pub fn required_resources(&self) -> BTreeSet<&Item<'a>> {
+        let factory_resources = self
+            .factory_planet
+            .map(|planet| planet.collect_resources())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|resource| {
+                self.products.iter().any(|product| {
+                    product.production.as_ref().is_some_and(|production| {
+                        production
+                            .inputs
+                            .iter()
+                            .any(|input| &input.item == *resource)
+                    })
+                })
+            });
+
+        self.planets
+            .iter()
+            .flat_map(|solution| &solution.products)
+            .flat_map(|product| product.root_resources())
+            .chain(factory_resources)
+            .collect()
+    }
+
+    /// Returns the labels of the production planets that supply inputs to `product`, sorted. Inputs
+    /// made by the factory itself are traced back through their own inputs until they reach items
+    /// supplied by a production planet.
+    pub fn origins(&self, product: &Item) -> Vec<&str> {
+        let mut origins = BTreeSet::new();
+        self.collect_origins(product, &mut origins);
+
+        origins.into_iter().collect()
+    }
+
+    fn collect_origins<'s>(&'s self, item: &Item, origins: &mut BTreeSet<&'s str>) {
+        let Some(production) = &item.production else {
+            return;
+        };
+
+        for input in &production.inputs {
+            let mut suppliers = self
+                .planets
+                .iter()
+                .filter(|solution| solution.products.contains(&input.item))
+                .peekable();
+
+            if suppliers.peek().is_none() {
+                self.collect_origins(&input.item, origins);
+            }
+
+            origins.extend(suppliers.map(|solution| solution.planet.label.as_str()));
+        }
+    }
+
+    /// Returns the number of products made at each tier, across both the production planets and
+    /// the factory. Tiers without any products are omitted.
+    pub fn tier_histogram(&self) -> BTreeMap<Tier, usize> {
+        let products: BTreeSet<_> = self
+            .planets
+            .iter()
+            .flat_map(|solution| &solution.products)
+            .chain(&self.products)
+            .collect();
+
+        products
+            .into_iter()
+            .counts_by(|product| product.tier)
+            .into_iter()
+            .collect()
+    }
+
+    /// Returns the solution's products that are not in `standalone`, which are the products that
+    /// only the factory planet makes possible. See [`Solver::standalone_products()`].
+    pub fn exclusive_products<'s>(&'s self, standalone: &BTreeSet<Item<'a>>) -> Vec<&'s Item<'a>> {
+        self.products
+            .iter()
+            .filter(|product| !standalone.contains(product))
+            .collect()
+    }
+
+    /// Returns the number of times a resource can be extracted on more than one of the solution's
+    /// planets. For example, a resource found on three planets adds two to the count. Lower values
+    /// indicate a more diverse set of planets.
+    pub fn resource_overlap(&self) -> usize {
+        planets_by_resource(self.planets.iter().map(|solution| solution.planet))
+            .values()
+            .map(|planets| planets.len() - 1)
+            .sum()
+    }
+}
+
+/// Groups the labels of `planets` by the labels of the resources they can extract.
+fn planets_by_resource<'p, I>(planets: I) -> BTreeMap<&'p str, Vec<&'p str>>
+where
+    I: IntoIterator<Item = &'p Planet<'p>>,
+{
+    let mut resources: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+    for planet in planets {
+        for resource in planet.collect_resources() {
+            resources
+                .entry(resource.label)
+                .or_default()
+                .push(&planet.label);
+        }
+    }
+
+    resources
+}
+
+/// A way to order factory solutions that [`FactorySolution::rank()`] considers equal.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum Tiebreak {
+    /// Highest combined resource density across the production planets first
+    Density,
+
+    /// Fewest production planets first
+    Planets,
+
+    /// Alphabetically by the production planets' labels
+    Labels,
+}
+
+impl Tiebreak {
+    /// The tiebreaks applied when none are given: density, then planets, then labels.
+    pub const DEFAULT: [Self; 3] = [Self::Density, Self::Planets, Self::Labels];
+
+    /// Compares two factory solutions by this tiebreak alone.
+    pub fn compare(self, a: &FactorySolution, b: &FactorySolution) -> Ordering {
+        let labels = |solution: &FactorySolution| {
+            solution
+                .planets
+                .iter()
+                .map(|s| s.planet.label.clone())
+                .collect::<Vec<_>>()
+        };
+
+        match self {
+            Self::Density => b.total_density().total_cmp(&a.total_density()),
+            Self::Planets => a.planets.len().cmp(&b.planets.len()),
+            Self::Labels => labels(a).cmp(&labels(b)),
+        }
+    }
+}
+
+/// An intermediate product that a factory consumes faster than it is supplied.
+#[derive(Debug, Clone, Serialize)]
+pub struct Shortfall<'a> {
+    pub item: Item<'a>,
+    /// Units per hour made by the production planets and the factory itself.
+    pub supply: u32,
+    /// Units per hour consumed by the factory's recipes.
+    pub demand: u32,
+}
+
+impl Shortfall<'_> {
+    /// Returns how many times the current supply is needed to meet demand, which is also how many
+    /// facilities the item needs for every one that the model assumes. Returns `None` if nothing
+    /// supplies the item.
+    pub fn ratio(&self) -> Option<f32> {
+        (self.supply > 0).then(|| self.demand as f32 / self.supply as f32)
+    }
+}
+
+/// Compares the hourly supply of each intermediate product against the hourly demand of the
+/// factory's recipes, assuming every planet runs one facility for each of its products. Imported
+/// items are assumed to always be available in sufficient quantity.
+fn find_shortfalls<'a>(
+    planets: &[Solution<'a>],
+    products: &BTreeSet<Item<'a>>,
+    imports: &BTreeSet<Item<'a>>,
+) -> Vec<Shortfall<'a>> {
+    let mut supply: HashMap<&Item, u32> = HashMap::new();
+
+    for product in planets
+        .iter()
+        .flat_map(|solution| &solution.products)
+        .chain(products)
+    {
+        if product.production.is_some() {
+            *supply.entry(product).or_default() += hourly_output(product);
+        }
+    }
+
+    let mut demand: HashMap<&Item, u32> = HashMap::new();
+
+    for product in products {
+        let Some(production) = &product.production else {
+            continue;
+        };
+
+        for input in &production.inputs {
+            if input.item.production.is_some() && !imports.contains(&input.item) {
+                *demand.entry(&input.item).or_default() +=
+                    u32::from(input.amount) * cycles_per_hour(product.tier);
+            }
+        }
+    }
+
+    demand
+        .into_iter()
+        .filter_map(|(item, demand)| {
+            let supply = supply.get(item).copied().unwrap_or_default();
+
+            (supply < demand).then(|| Shortfall {
+                item: item.clone(),
+                supply,
+                demand,
+            })
+        })
+        .sorted_by_key(|shortfall| shortfall.item.id)
+        .collect()
+}
+
+/// Returns the number of units of `item` a single facility makes per hour, or zero if the item has
+/// no production information.
+fn hourly_output(item: &Item) -> u32 {
+    item.production.as_ref().map_or(0, |production| {
+        u32::from(production.quantity) * cycles_per_hour(item.tier)
+    })
+}
+
+/// Basic industry facilities (P1) run on a 30 minute cycle, while all other facilities run hourly.
+fn cycles_per_hour(tier: Tier) -> u32 {
+    match tier {
+        Tier::P1 => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::System;
+    use std::str::FromStr;
+    use std::{env, process};
+
+    const ITEMS: &str = "
+a: { label: A, tier: r0 }
+b: { label: B, tier: r0 }
+pa: { label: PA, tier: p1, production: { quantity: 20, inputs: { a: 3000 } } }
+pb: { label: PB, tier: p1, production: { quantity: 20, inputs: { b: 3000 } } }
+x: { label: X, tier: p2, production: { quantity: 5, inputs: { pa: 40, pb: 40 } } }
+y: { label: Y, tier: p2, production: { quantity: 5, inputs: { pa: 40 } } }
+z: { label: Z, tier: p3, production: { quantity: 3, inputs: { x: 10, y: 10 } } }
+";
+
+    const SYSTEM: &str = "
+label: Test
+planets:
+    - { label: Test I, resources: { a: 0.5 } }
+    - { label: Test II, resources: { b: 0.5 } }
+";
+
+    /// Returns a solution for `planet` that makes the items with the given IDs.
+    fn solution<'a>(
+        planet: &'a Planet<'a>,
+        ids: &[&str],
+        item_manager: &'a ItemManager,
+    ) -> Solution<'a> {
+        let products = ids.iter().map(|id| item_manager.get(id).unwrap()).collect();
+        Solution::new(planet, products)
+    }
+
+    /// Returns a factory solution fed by `planets`, without any products of its own.
+    fn factory(planets: Vec<Solution>) -> FactorySolution {
+        FactorySolution {
+            planets,
+            factory_planet: None,
+            products: BTreeSet::new(),
+            imports: BTreeSet::new(),
+            balanced: true,
+            shortfalls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn max_tier_precedence() {
+        use Tier::*;
+
+        // (max_tier, production_max_tier, factory_max_tier) => (production, factory)
+        let cases = [
+            ((None, None, None), (P1, P4)),
+            ((Some(P2), None, None), (P2, P2)),
+            ((Some(P2), Some(P1), None), (P1, P2)),
+            ((Some(P2), None, Some(P3)), (P2, P3)),
+            ((Some(P2), Some(P1), Some(P3)), (P1, P3)),
+            ((None, Some(P2), None), (P2, P4)),
+            ((None, None, Some(P3)), (P1, P3)),
+            ((None, Some(P2), Some(P3)), (P2, P3)),
+        ];
+
+        for ((max, production, factory), expected) in cases {
+            let solver = Solver::builder()
+                .max_tier(max)
+                .production_max_tier(production)
+                .factory_max_tier(factory)
+                .build();
+
+            assert_eq!(
+                (solver.production_max_tier(), solver.factory_max_tier()),
+                expected,
+                "max {max:?}, production {production:?}, factory {factory:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn excluding_an_item_blocks_its_dependents() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let solve = |exclude: &[&str]| {
+            Solver::builder()
+                .max_planets(3)
+                .exclude_items(exclude.iter().map(|id| id.to_string()).collect())
+                .show_progress(false)
+                .build()
+                .solve(&system, &item_manager)
+        };
+
+        let simulation = solve(&[]);
+        assert!(simulation.reachable_products().contains("z"));
+
+        let simulation = solve(&["x"]);
+        let products = simulation.reachable_products();
+        assert!(!products.contains("x"));
+        assert!(!products.contains("z"));
+        assert!(products.contains("y"));
+    }
+
+    #[test]
+    fn planets_only_skips_factories() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let solver = Solver::builder()
+            .planets_only()
+            .show_progress(false)
+            .build();
+
+        assert_eq!(solver.production_max_tier(), Tier::P4);
+
+        let simulation = solver.solve(&system, &item_manager);
+        assert!(simulation.factory_solutions.is_empty());
+        assert_eq!(simulation.best_mode(), SolutionMode::PerPlanet);
+    }
+
+    #[test]
+    fn solving_is_deterministic() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let solver = Solver::builder()
+            .max_planets(3)
+            .show_progress(false)
+            .build();
+
+        let solve = || serde_json::to_string(&solver.solve(&system, &item_manager)).unwrap();
+        assert_eq!(solve(), solve());
+    }
+
+    #[test]
+    fn solve_cycles_excludes_r0_and_includes_max_tier() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let solver = Solver::builder().show_progress(false).build();
+        let resources: Vec<_> = ["a", "b"]
+            .into_iter()
+            .map(|id| item_manager.get(id).unwrap())
+            .collect();
+
+        let solve = |max_tier| {
+            solver
+                .solve_cycles(&resources.iter().collect(), &item_manager, max_tier)
+                .into_iter()
+                .map(|item| item.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(solve(Tier::P1), ["pa", "pb"]);
+        assert_eq!(solve(Tier::P2), ["pa", "pb", "x", "y"]);
+        assert_eq!(solve(Tier::P3), ["pa", "pb", "x", "y", "z"]);
+    }
+
+    #[test]
+    fn best_planet_prefers_tier_then_product_count_then_density() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(
+            "
+label: Test
+planets:
+    - { label: Test I, resources: { a: 0.9 } }
+    - { label: Test II, resources: { a: 0.1 } }
+",
+            None,
+            &item_manager,
+        )
+        .unwrap();
+
+        let [dense, sparse] = [&system.planets[0], &system.planets[1]];
+        let best = |solutions: Vec<Solution<'_>>| {
+            let simulation = Simulation {
+                planet_solutions: solutions,
+                ..Default::default()
+            };
+
+            let best = simulation.best_planet().unwrap();
+            (best.planet.label.clone(), best.products.len())
+        };
+
+        let by_tier = vec![
+            solution(dense, &["pa", "pb"], &item_manager),
+            solution(sparse, &["y"], &item_manager),
+        ];
+        assert_eq!(best(by_tier), ("Test II".to_owned(), 1));
+
+        let by_count = vec![
+            solution(dense, &["pa"], &item_manager),
+            solution(sparse, &["pa", "pb"], &item_manager),
+        ];
+        assert_eq!(best(by_count), ("Test II".to_owned(), 2));
+
+        let by_density = vec![
+            solution(sparse, &["pa"], &item_manager),
+            solution(dense, &["pa"], &item_manager),
+            solution(sparse, &["pa"], &item_manager),
+        ];
+        assert_eq!(best(by_density), ("Test I".to_owned(), 1));
+    }
+
+    #[test]
+    fn redundant_planets_can_all_be_removed_together() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(
+            "
+label: Test
+planets:
+    - { label: Test I, resources: { a: 0.5 } }
+    - { label: Test II, resources: { a: 0.5 } }
+    - { label: Test III, resources: { b: 0.5 } }
+",
+            None,
+            &item_manager,
+        )
+        .unwrap();
+
+        let solution = factory(vec![
+            solution(&system.planets[0], &["pa"], &item_manager),
+            solution(&system.planets[1], &["pa"], &item_manager),
+            solution(&system.planets[2], &["pb"], &item_manager),
+        ]);
+
+        let redundant: Vec<_> = solution
+            .redundant_planets()
+            .into_iter()
+            .map(|solution| solution.planet.label.as_str())
+            .collect();
+
+        assert_eq!(redundant, ["Test I"]);
+    }
+
+    #[test]
+    fn saved_simulation_round_trips() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let solver = Solver::builder()
+            .max_planets(2)
+            .imports(vec!["y".to_owned()])
+            .factory_planet("Test II".to_owned())
+            .show_progress(false)
+            .build();
+
+        let path = env::temp_dir().join(format!("pi2-round-trip-{}.json", process::id()));
+        let simulation = solver.solve(&system, &item_manager);
+        simulation.save(&path).unwrap();
+
+        let loaded = solver.load(&path, &system, &item_manager);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        let summary = |simulation: &Simulation| {
+            let solutions: Vec<_> = simulation
+                .factory_solutions
+                .iter()
+                .map(|solution| {
+                    (
+                        owned_ids(&solution.products),
+                        owned_ids(&solution.imports),
+                        solution.factory_planet.map(|planet| planet.label.clone()),
+                    )
+                })
+                .collect();
+
+            (
+                simulation.mode,
+                solutions,
+                simulation.input_fingerprint.clone(),
+            )
+        };
+
+        let (mode, solutions, _) = summary(&simulation);
+        assert_eq!(mode, SolutionMode::Factory);
+        assert!(!solutions.is_empty());
+        assert!(solutions.iter().all(|(_, imports, factory_planet)| {
+            imports == &["y"] && factory_planet.as_deref() == Some("Test II")
+        }));
+
+        assert_eq!(summary(&loaded), summary(&simulation));
+    }
+
+    #[test]
+    fn loading_a_stale_or_unresolvable_simulation_fails() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let solver = Solver::builder()
+            .max_planets(3)
+            .show_progress(false)
+            .build();
+
+        let path = env::temp_dir().join(format!("pi2-stale-{}.json", process::id()));
+        let mut simulation = OwnedSimulation::from(&solver.solve(&system, &item_manager));
+        simulation.save(&path).unwrap();
+
+        let stale = Solver::builder()
+            .max_planets(2)
+            .show_progress(false)
+            .build()
+            .load(&path, &system, &item_manager);
+
+        simulation.factory_solutions[0]
+            .products
+            .push("not_an_item".to_owned());
+        simulation.save(&path).unwrap();
+
+        let unresolvable = solver.load(&path, &system, &item_manager);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(stale, Err(error::Error::StaleSimulation)));
+        assert!(matches!(unresolvable, Err(error::Error::MissingItem(id)) if id == "not_an_item"));
+    }
+
+    #[test]
+    fn diff_matches_solutions_by_planet_labels() {
+        fn simulation<'a>(
+            system: &'a System<'a>,
+            products: [&[&str]; 2],
+            item_manager: &'a ItemManager,
+        ) -> Simulation<'a> {
+            Simulation {
+                planet_solutions: system
+                    .planets
+                    .iter()
+                    .zip(products)
+                    .map(|(planet, ids)| solution(planet, ids, item_manager))
+                    .collect(),
+                ..Default::default()
+            }
+        }
+
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let before = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let after = System::parse(SYSTEM, None, &item_manager).unwrap();
+
+        let before = simulation(&before, [&["pa", "y"], &["pb"]], &item_manager);
+        let after = simulation(&after, [&["pa", "x"], &["pb"]], &item_manager);
+        let diff = before.diff(&after);
+
+        assert_eq!(diff.solutions.len(), 1);
+
+        let solution = &diff.solutions[0];
+        let ids = |items: &[&Item]| -> Vec<String> {
+            items.iter().map(|item| item.id.to_owned()).collect()
+        };
+
+        assert_eq!(solution.planets, ["Test I"]);
+        assert_eq!(ids(&solution.added), ["x"]);
+        assert_eq!(ids(&solution.removed), ["y"]);
+        assert!(before.diff(&before).is_empty());
+    }
+
+    #[test]
+    fn minimize_planets_counts_the_factory() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let solver = Solver::builder().show_progress(false).build();
+        let labels = |solutions: &[Solution]| -> Vec<String> {
+            solutions
+                .iter()
+                .map(|solution| solution.planet.label.clone())
+                .collect()
+        };
+
+        let minimal = solver.minimize_planets(&["pa"], &system, &item_manager);
+        let Some(MinimalPlanets::PerPlanet(solutions)) = &minimal else {
+            panic!("expected a per-planet result, got {minimal:?}");
+        };
+        assert_eq!(labels(solutions), ["Test I"]);
+        assert_eq!(minimal.unwrap().planet_count(), 1);
+
+        let minimal = solver.minimize_planets(&["x"], &system, &item_manager);
+        let Some(MinimalPlanets::Factory(solution)) = &minimal else {
+            panic!("expected a factory result, got {minimal:?}");
+        };
+        assert_eq!(labels(&solution.planets), ["Test I", "Test II"]);
+        assert_eq!(minimal.unwrap().planet_count(), 3);
+
+        let pinned = Solver::builder()
+            .factory_planet("Test II".to_owned())
+            .show_progress(false)
+            .build()
+            .minimize_planets(&["x"], &system, &item_manager);
+        let Some(MinimalPlanets::Factory(solution)) = &pinned else {
+            panic!("expected a factory result, got {pinned:?}");
+        };
+        assert_eq!(labels(&solution.planets), ["Test I"]);
+        assert_eq!(pinned.unwrap().planet_count(), 2);
+    }
+
+    #[test]
+    fn minimize_planets_fails_for_unknown_or_unreachable_targets() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let solver = Solver::builder().show_progress(false).build();
+
+        assert!(solver
+            .minimize_planets(&["not_an_item"], &system, &item_manager)
+            .is_none());
+
+        let planets_only = Solver::builder()
+            .use_factory_planet(false)
+            .show_progress(false)
+            .build();
+
+        assert!(planets_only
+            .minimize_planets(&["x"], &system, &item_manager)
+            .is_none());
+    }
+
+    #[test]
+    fn shortfalls_compare_hourly_supply_and_demand() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let planets = [
+            solution(&system.planets[0], &["pa"], &item_manager),
+            solution(&system.planets[1], &["pb"], &item_manager),
+        ];
+
+        let items = |ids: &[&str]| -> BTreeSet<Item> {
+            ids.iter().map(|id| item_manager.get(id).unwrap()).collect()
+        };
+
+        // Both P1 are made at 40 units an hour, which is exactly what X consumes.
+        let balanced = find_shortfalls(&planets, &items(&["x"]), &BTreeSet::new());
+        assert!(balanced.is_empty());
+
+        // Y consumes another 40 PA an hour.
+        let shortfalls = find_shortfalls(&planets, &items(&["x", "y"]), &BTreeSet::new());
+        let [shortfall] = shortfalls.as_slice() else {
+            panic!("expected one shortfall, got {shortfalls:?}");
+        };
+
+        assert_eq!(shortfall.item.id, "pa");
+        assert_eq!((shortfall.supply, shortfall.demand), (40, 80));
+        assert_eq!(shortfall.ratio(), Some(2.0));
+
+        let imported = find_shortfalls(&planets, &items(&["x", "y"]), &items(&["pa"]));
+        assert!(imported.is_empty());
+
+        let unsupplied = Shortfall {
+            supply: 0,
+            ..shortfall.clone()
+        };
+        assert_eq!(unsupplied.ratio(), None);
+    }
+
+    #[test]
+    fn suggested_assignment_makes_each_overlap_once() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(
+            "
+label: Test
+planets:
+    - { label: Test I, resources: { a: 0.5, b: 0.5 } }
+    - { label: Test II, resources: { a: 0.5, b: 0.5 } }
+",
+            None,
+            &item_manager,
+        )
+        .unwrap();
+
+        let solution = factory(vec![
+            solution(&system.planets[0], &["pa", "pb"], &item_manager),
+            solution(&system.planets[1], &["pa", "pb"], &item_manager),
+        ]);
+
+        let overlaps: Vec<_> = solution
+            .production_overlaps()
+            .into_iter()
+            .map(|(item, planets)| (item.id, planets))
+            .collect();
+
+        assert_eq!(
+            overlaps,
+            [
+                ("pa", vec!["Test I", "Test II"]),
+                ("pb", vec!["Test I", "Test II"]),
+            ]
+        );
+
+        let assignment: Vec<_> = solution
+            .suggested_assignment()
+            .into_iter()
+            .map(|(item, planet)| (item.id, planet))
+            .collect();
+
+        assert_eq!(assignment, [("pa", "Test I"), ("pb", "Test II")]);
+    }
+
+    #[test]
+    fn tiebreaks_apply_in_the_order_given() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(
+            "
+label: Test
+planets:
+    - { label: Test I, resources: { a: 0.5 } }
+    - { label: Test II, resources: { b: 0.5 } }
+    - { label: Test III, resources: { a: 0.25, b: 0.25 } }
+",
+            None,
+            &item_manager,
+        )
+        .unwrap();
+
+        // Both make the same products, but the first is denser and the second uses fewer planets.
+        let dense = factory(vec![
+            solution(&system.planets[0], &["pa"], &item_manager),
+            solution(&system.planets[1], &["pb"], &item_manager),
+        ]);
+        let small = factory(vec![solution(
+            &system.planets[2],
+            &["pa", "pb"],
+            &item_manager,
+        )]);
+
+        assert_eq!(dense.rank(&small, &[]), Ordering::Equal);
+
+        let by_density = [Tiebreak::Density, Tiebreak::Planets];
+        assert_eq!(dense.rank(&small, &by_density), Ordering::Less);
+        assert_eq!(dense.rank(&small, &Tiebreak::DEFAULT), Ordering::Less);
+
+        let by_planets = [Tiebreak::Planets, Tiebreak::Density];
+        assert_eq!(dense.rank(&small, &by_planets), Ordering::Greater);
+    }
+
+    #[test]
+    fn planet_labels_match_substrings_and_whole_numerals() {
+        assert!(matches_planet_label("Tanoo V", "tanoo v"));
+        assert!(!matches_planet_label("Tanoo VI", "tanoo v"));
+        assert!(matches_planet_label("Tanoo VI", "TANOO VI"));
+        assert!(matches_planet_label("Tanoo VI", "noo"));
+        assert!(matches_planet_label("Tanoo VI - Moon 3", "tanoo vi"));
+
+        for numeral in ["i", "iv", "ix", "xiv", "xxxix"] {
+            assert!(is_planet_numeral(numeral), "{numeral}");
+        }
+
+        // Words made of numeral letters that aren't numerals may match anywhere.
+        for word in ["dim", "mid", "mix", "ill", "vivid", "iiii", "xl"] {
+            assert!(!is_planet_numeral(word), "{word}");
+        }
+
+        assert!(matches_planet_label("Dimmed I", "dim"));
+    }
+
+    #[test]
+    fn unmatched_include_planets_are_reported() {
+        let item_manager = ItemManager::from_str(ITEMS).unwrap();
+        let system = System::parse(SYSTEM, None, &item_manager).unwrap();
+        let solver = Solver::builder()
+            .include_planets(vec!["test i".to_owned(), "Nowhere".to_owned()])
+            .build();
+
+        assert_eq!(solver.unmatched_include_planets(&system), ["Nowhere"]);
+    }
+}
@@ -1,155 +1,561 @@
-use crate::error::{Error, Result};
-use crate::item::{Item, ItemManager};
-use log::debug;
-use serde::Deserialize;
-use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
-use std::fmt::{Display, Formatter};
-use std::fs;
-use std::hash::{Hash, Hasher};
-use std::path::Path;
-
-#[derive(Debug)]
-pub struct System<'a> {
-    pub label: String,
-    pub planets: Vec<Planet<'a>>,
-}
-
-impl<'a> System<'a> {
-    pub fn new<P>(system_path: P, item_manager: &'a ItemManager) -> Result<Self>
-    where
-        P: AsRef<Path>,
-    {
-        let raw: RawSystem = serde_yaml::from_str(&fs::read_to_string(system_path)?)?;
-        let system = Self {
-            label: raw.label,
-            planets: raw
-                .planets
-                .into_iter()
-                .map(|raw| Planet::from_raw(raw, item_manager))
-                .collect::<Result<_>>()?,
-        };
-
-        debug!(
-            "System {} initialized with {} planet(s)",
-            system.label,
-            system.planets.len()
-        );
-
-        Ok(system)
-    }
-}
-
-impl Display for System<'_> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} ({} planets)", self.label, self.planets.len())
-    }
-}
-
-#[derive(Debug)]
-pub struct Planet<'a> {
-    pub label: String,
-    pub resources: Vec<Resource<'a>>,
-}
-
-impl Display for Planet<'_> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}", self.label)?;
-
-        for resource in &self.resources {
-            writeln!(
-                f,
-                "  {}: {:.0}%",
-                resource.item.label,
-                resource.density * 100.0
-            )?;
-        }
-
-        Ok(())
-    }
-}
-
-impl<'a> Planet<'a> {
-    fn from_raw(raw_planet: RawPlanet, item_manager: &'a ItemManager) -> Result<Self> {
-        let mut resources: Vec<Resource<'a>> = Vec::new();
-
-        for (item_id, density) in raw_planet.resources {
-            resources.push(Resource {
-                density,
-                item: match item_manager.get(&item_id) {
-                    Some(item) => item,
-                    None => return Err(Error::create_missing_item(item_id)),
-                },
-            })
-        }
-
-        Ok(Self {
-            label: raw_planet.label,
-            resources,
-        })
-    }
-
-    pub fn collect_resources(&self) -> HashSet<&Item<'a>> {
-        self.resources.iter().map(|res| &res.item).collect()
-    }
-}
-
-impl Hash for Planet<'_> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.label.hash(state)
-    }
-}
-
-impl PartialEq for Planet<'_> {
-    fn eq(&self, other: &Self) -> bool {
-        self.label == other.label
-    }
-}
-
-impl Eq for Planet<'_> {}
-
-impl Ord for Planet<'_> {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.label.cmp(&other.label)
-    }
-}
-
-impl PartialOrd for Planet<'_> {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct Resource<'a> {
-    pub item: Item<'a>,
-    pub density: f32,
-}
-
-#[derive(Debug, Deserialize)]
-struct RawSystem {
-    label: String,
-    planets: Vec<RawPlanet>,
-}
-
-#[derive(Debug, Deserialize)]
-struct RawPlanet {
-    label: String,
-    resources: HashMap<String, f32>,
-}
-
-pub trait IterPlanets {
-    fn iter_planets(&self) -> impl Iterator<Item = &Planet>;
-}
-
-impl IterPlanets for System<'_> {
-    fn iter_planets(&self) -> impl Iterator<Item = &Planet> {
-        self.planets.iter()
-    }
-}
-
-impl IterPlanets for Vec<Planet<'_>> {
-    fn iter_planets(&self) -> impl Iterator<Item = &Planet> {
-        self.iter()
-    }
-}
+use crate::error::{Error, Result};
+use crate::facility::{Budget, MAX_COMMAND_CENTER_LEVEL};
+use crate::input;
+use crate::item::{Item, ItemManager};
+use crate::Tier;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub struct System<'a> {
+    pub label: String,
+    /// How many jumps the system is from the system of the factory planet.
+    pub jumps_from_factory: u32,
+    pub planets: Vec<Planet<'a>>,
+}
+
+impl<'a> System<'a> {
+    /// Loads a system from `system_path`. Files with a `.json` extension are read with
+    /// [`System::from_scanner_json()`], and everything else as YAML. If a `scan` is given, its
+    /// densities replace those in the system file.
+    pub fn new<P>(
+        system_path: P,
+        scan: Option<&Scan>,
+        item_manager: &'a ItemManager,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_raw(RawSystem::load(system_path)?, scan, item_manager)
+    }
+
+    /// Parses a system from a YAML string rather than reading it from a file. Unlike
+    /// [`ItemManager`], a system needs items to resolve against, so it can't implement
+    /// [`std::str::FromStr`].
+    pub fn parse(
+        contents: &str,
+        scan: Option<&Scan>,
+        item_manager: &'a ItemManager,
+    ) -> Result<Self> {
+        Self::from_raw(serde_yaml::from_str(contents)?, scan, item_manager)
+    }
+
+    /// Parses a system from the flat JSON array exported by third-party PI scanners. Each entry
+    /// describes one planet, with the resource IDs it extracts and their outputs as densities from
+    /// 0 to 1. The system is labelled by the `system` field of the first entry, which is optional.
+    ///
+    /// ```json
+    /// [
+    ///     {
+    ///         "system": "J103326",
+    ///         "planet": "J103326 I",
+    ///         "resources": [
+    ///             { "name": "base_metals", "output": 0.31 },
+    ///             { "name": "heavy_metals", "output": 0.88 }
+    ///         ]
+    ///     }
+    /// ]
+    /// ```
+    ///
+    /// Command center levels cannot be given, so every planet is assumed to be fully upgraded.
+    pub fn from_scanner_json(
+        contents: &str,
+        scan: Option<&Scan>,
+        item_manager: &'a ItemManager,
+    ) -> Result<Self> {
+        Self::from_raw(RawSystem::from_scanner_json(contents)?, scan, item_manager)
+    }
+
+    fn from_raw(
+        raw: RawSystem,
+        scan: Option<&Scan>,
+        item_manager: &'a ItemManager,
+    ) -> Result<Self> {
+        if raw.planets.is_empty() {
+            return Err(Error::EmptySystem(raw.label));
+        }
+
+        let jumps_from_factory = raw.jumps_from_factory;
+
+        let system = Self {
+            label: raw.label,
+            jumps_from_factory,
+            planets: raw
+                .planets
+                .into_iter()
+                .map(|raw| {
+                    Planet::from_raw(raw, scan, item_manager).map(|planet| Planet {
+                        jumps_from_factory,
+                        ..planet
+                    })
+                })
+                .collect::<Result<_>>()?,
+        };
+
+        debug!(
+            "System {} initialized with {} planet(s)",
+            system.label,
+            system.planets.len()
+        );
+
+        Ok(system)
+    }
+
+    /// Checks the system file at `system_path` against `item_manager` without stopping at the
+    /// first problem, returning every problem found. An empty list means [`System::new()`] would
+    /// succeed. Files that cannot be read or parsed still fail immediately.
+    pub fn lint<P>(system_path: P, item_manager: &ItemManager) -> Result<Vec<Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let raw = RawSystem::load(system_path)?;
+        let mut problems = Vec::new();
+
+        if raw.planets.is_empty() {
+            problems.push(Error::EmptySystem(raw.label));
+        }
+
+        for raw_planet in &raw.planets {
+            problems.extend(raw_planet.problems(item_manager));
+        }
+
+        Ok(problems)
+    }
+
+    /// Returns `true` if any item of `tier` can be made from the resources extracted across every
+    /// planet in the system. This only checks which recipes are reachable from those resources,
+    /// ignoring planet counts and facility limits, so it is much cheaper than solving.
+    pub fn can_produce_tier(&self, tier: Tier, item_manager: &ItemManager) -> bool {
+        let mut reachable: BTreeSet<Item> = self
+            .planets
+            .iter()
+            .flat_map(|planet| planet.collect_resources())
+            .cloned()
+            .collect();
+
+        let mut frontier: Vec<Item> = reachable.iter().cloned().collect();
+
+        while let Some(item) = frontier.pop() {
+            if item.tier == tier {
+                return true;
+            }
+
+            for product in item_manager.get_products(&item).unwrap_or_default() {
+                if reachable.contains(&product) {
+                    continue;
+                }
+
+                let is_makeable = product.production.as_ref().is_some_and(|production| {
+                    production
+                        .inputs
+                        .iter()
+                        .all(|input| reachable.contains(&input.item))
+                });
+
+                if is_makeable {
+                    reachable.insert(product.clone());
+                    frontier.push(product);
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Combines this system with `others`, keeping every planet as-is. Resources found on several
+    /// planets are not merged, so each planet still extracts only its own resources at its own
+    /// densities, and keeps the jumps from the factory of the system it came from. The merged
+    /// system's label joins the labels of every system, and its jumps are those of this system.
+    pub fn merge(mut self, others: Vec<System<'a>>) -> Self {
+        for other in others {
+            self.label = format!("{}, {}", self.label, other.label);
+            self.planets.extend(other.planets);
+        }
+
+        self
+    }
+}
+
+impl Display for System<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({} planets)", self.label, self.planets.len())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Planet<'a> {
+    pub label: String,
+    pub resources: Vec<Resource<'a>>,
+    /// The CPU and powergrid provided by the planet's command center.
+    pub budget: Budget,
+    /// How many jumps the planet's system is from the system of the factory planet.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub jumps_from_factory: u32,
+    /// Storage planets are only used for logistics, so they extract nothing, but may still be
+    /// the factory planet.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub storage: bool,
+}
+
+impl Display for Planet<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.with_extraction_rate(None).fmt(f)
+    }
+}
+
+/// Displays a planet, optionally including an estimated extraction rate for each resource. See
+/// [`Planet::with_extraction_rate()`].
+pub struct PlanetDisplay<'p, 'a> {
+    planet: &'p Planet<'a>,
+    base_rate: Option<u32>,
+    max_tier: Option<Tier>,
+}
+
+impl PlanetDisplay<'_, '_> {
+    /// Shows `max_tier` as the highest tier the planet can make, as in "Tanoo IV: up to P2".
+    pub fn with_max_tier(mut self, max_tier: Option<Tier>) -> Self {
+        self.max_tier = max_tier;
+        self
+    }
+}
+
+impl Display for PlanetDisplay<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.max_tier {
+            Some(max_tier) => writeln!(f, "{}: up to {max_tier:?}", self.planet.label)?,
+            None if self.planet.storage => writeln!(f, "{} (storage)", self.planet.label)?,
+            None => writeln!(f, "{}", self.planet.label)?,
+        }
+
+        for resource in &self.planet.resources {
+            write!(
+                f,
+                "  {}: {:.0}%",
+                resource.item.label,
+                resource.density * 100.0
+            )?;
+
+            if let Some(base_rate) = self.base_rate {
+                write!(f, " (~{}/hr)", resource.estimated_rate(base_rate))?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Planet<'a> {
+    fn from_raw(
+        raw_planet: RawPlanet,
+        scan: Option<&Scan>,
+        item_manager: &'a ItemManager,
+    ) -> Result<Self> {
+        if let Some(problem) = raw_planet.problems(item_manager).into_iter().next() {
+            return Err(problem);
+        }
+
+        let budget = Budget::for_command_center(raw_planet.command_center_level())
+            .expect("command center level should have been checked");
+
+        let resources = raw_planet
+            .resources
+            .into_iter()
+            .filter_map(|(item_id, density)| {
+                let density = scan
+                    .and_then(|scan| scan.density(&raw_planet.label, &item_id))
+                    .unwrap_or(density);
+
+                let item = item_manager.get(&item_id)?;
+
+                Some(Resource { density, item })
+            })
+            .collect();
+
+        Ok(Self {
+            label: raw_planet.label,
+            resources,
+            budget,
+            jumps_from_factory: 0,
+            storage: raw_planet.storage,
+        })
+    }
+
+    /// Returns a [`Display`] implementation for the planet that, if `base_rate` is provided, also
+    /// shows each resource's [`Resource::estimated_rate()`].
+    pub fn with_extraction_rate(&self, base_rate: Option<u32>) -> PlanetDisplay<'_, 'a> {
+        PlanetDisplay {
+            planet: self,
+            base_rate,
+            max_tier: None,
+        }
+    }
+
+    /// Returns the resources the planet extracts, which is none for a storage planet.
+    pub fn collect_resources(&self) -> BTreeSet<&Item<'a>> {
+        if self.storage {
+            return BTreeSet::new();
+        }
+
+        self.resources.iter().map(|res| &res.item).collect()
+    }
+}
+
+impl Hash for Planet<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.label.hash(state)
+    }
+}
+
+impl PartialEq for Planet<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.label == other.label
+    }
+}
+
+impl Eq for Planet<'_> {}
+
+impl Ord for Planet<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.label.cmp(&other.label)
+    }
+}
+
+impl PartialOrd for Planet<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Resource<'a> {
+    pub item: Item<'a>,
+    pub density: f32,
+}
+
+impl Resource<'_> {
+    /// Estimates the units extracted per hour by scaling `base_rate`, the hourly output of an
+    /// extractor on a resource at 100% density, by the resource's density.
+    pub fn estimated_rate(&self, base_rate: u32) -> u32 {
+        (f64::from(base_rate) * f64::from(self.density)).round() as u32
+    }
+}
+
+/// Resource densities from a survey scan, keyed by planet label and then resource ID. Passing a
+/// scan to [`System::new()`] lets one system file be reused as densities change, since any
+/// density found in the scan replaces the one in the system file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Scan(BTreeMap<String, BTreeMap<String, f32>>);
+
+impl Scan {
+    pub fn new<P>(scan_path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        input::read_to_string(scan_path)?.parse()
+    }
+
+    /// Returns the scanned density of the resource with the ID `item_id` on the planet labelled
+    /// `planet`, if the scan has one.
+    pub fn density(&self, planet: &str, item_id: &str) -> Option<f32> {
+        self.0.get(planet)?.get(item_id).copied()
+    }
+}
+
+impl FromStr for Scan {
+    type Err = Error;
+
+    fn from_str(contents: &str) -> Result<Self> {
+        Ok(serde_yaml::from_str(contents)?)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawSystem {
+    label: String,
+    #[serde(default)]
+    jumps_from_factory: u32,
+    planets: Vec<RawPlanet>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawPlanet {
+    label: String,
+    resources: BTreeMap<String, f32>,
+    /// Assumed to be fully upgraded if not provided.
+    command_center_level: Option<u8>,
+    #[serde(default)]
+    storage: bool,
+}
+
+impl RawSystem {
+    /// Reads the system file at `path`, as JSON from a scanner if it has a `.json` extension, or
+    /// as YAML otherwise.
+    fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let is_json = path
+            .as_ref()
+            .extension()
+            .is_some_and(|extension| extension == "json");
+
+        let contents = input::read_to_string(path)?;
+
+        if is_json {
+            Self::from_scanner_json(&contents)
+        } else {
+            Ok(serde_yaml::from_str(&contents)?)
+        }
+    }
+
+    fn from_scanner_json(contents: &str) -> Result<Self> {
+        let entries: Vec<ScannerPlanet> =
+            serde_json::from_str(contents).map_err(Error::JsonDeserialize)?;
+
+        let label = entries
+            .first()
+            .and_then(|entry| entry.system.clone())
+            .unwrap_or_else(|| SCANNED_SYSTEM_LABEL.to_string());
+
+        Ok(Self {
+            label,
+            jumps_from_factory: 0,
+            planets: entries
+                .into_iter()
+                .map(|entry| RawPlanet {
+                    label: entry.planet,
+                    resources: entry
+                        .resources
+                        .into_iter()
+                        .map(|resource| (resource.name, resource.output))
+                        .collect(),
+                    command_center_level: None,
+                    storage: false,
+                })
+                .collect(),
+        })
+    }
+}
+
+impl RawPlanet {
+    fn command_center_level(&self) -> u8 {
+        self.command_center_level
+            .unwrap_or(MAX_COMMAND_CENTER_LEVEL)
+    }
+
+    /// Returns every reason the planet cannot be loaded by [`Planet::from_raw()`].
+    fn problems(&self, item_manager: &ItemManager) -> Vec<Error> {
+        let mut problems = Vec::new();
+
+        for item_id in self.resources.keys() {
+            match item_manager.get(item_id) {
+                None => problems.push(Error::create_missing_item(item_id)),
+                Some(item) if item.tier != Tier::R0 => problems.push(Error::NonResourceItem {
+                    planet: self.label.clone(),
+                    item: item_id.clone(),
+                }),
+                Some(_) => (),
+            }
+        }
+
+        let level = self.command_center_level();
+
+        if Budget::for_command_center(level).is_none() {
+            problems.push(Error::InvalidCommandCenterLevel {
+                planet: self.label.clone(),
+                level,
+            });
+        }
+
+        problems
+    }
+}
+
+/// Labels systems read by [`System::from_scanner_json()`] that do not name their system.
+const SCANNED_SYSTEM_LABEL: &str = "Scanned system";
+
+#[derive(Debug, Deserialize)]
+struct ScannerPlanet {
+    system: Option<String>,
+    planet: String,
+    resources: Vec<ScannerResource>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScannerResource {
+    name: String,
+    output: f32,
+}
+
+pub trait IterPlanets {
+    fn iter_planets(&self) -> impl Iterator<Item = &Planet<'_>>;
+}
+
+impl IterPlanets for System<'_> {
+    fn iter_planets(&self) -> impl Iterator<Item = &Planet<'_>> {
+        self.planets.iter()
+    }
+}
+
+impl IterPlanets for Vec<Planet<'_>> {
+    fn iter_planets(&self) -> impl Iterator<Item = &Planet<'_>> {
+        self.iter()
+    }
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn merge_keeps_overlapping_resources_distinct() {
+        let item_manager = ItemManager::from_str(
+            "
+a: { label: A, tier: r0 }
+b: { label: B, tier: r0 }
+",
+        )
+        .unwrap();
+
+        let parse = |contents| System::parse(contents, None, &item_manager).unwrap();
+        let system = parse("{ label: One, planets: [{ label: One I, resources: { a: 0.5 } }] }")
+            .merge(vec![parse(
+                "{ label: Two, planets: [{ label: Two I, resources: { a: 0.3, b: 0.4 } }] }",
+            )]);
+
+        assert_eq!(system.label, "One, Two");
+        assert_eq!(system.planets.len(), 2);
+
+        let densities: Vec<_> = system
+            .planets
+            .iter()
+            .flat_map(|planet| &planet.resources)
+            .filter(|resource| resource.item.id == "a")
+            .map(|resource| resource.density)
+            .collect();
+
+        assert_eq!(densities, [0.5, 0.3]);
+
+        let resources: BTreeSet<_> = system
+            .iter_planets()
+            .flat_map(|planet| planet.collect_resources())
+            .map(|item| item.id)
+            .collect();
+
+        assert_eq!(resources, BTreeSet::from(["a", "b"]));
+    }
+}
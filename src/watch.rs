@@ -0,0 +1,87 @@
+use crate::cli::SolveArgs;
+use crate::{items_paths, solve};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use pi2::{error, input};
+use std::collections::BTreeSet;
+use std::io;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// How long to wait for more changes before solving again, since editors often save a file in
+/// several steps.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Solves once, then again whenever any system, items, or scan file named in `args` changes, until
+/// the process is interrupted.
+pub fn run(args: &SolveArgs) -> error::Result<()> {
+    let files: BTreeSet<PathBuf> = iter::once(args.input.system.clone())
+        .chain(args.with_system.iter().cloned())
+        .chain(items_paths(&args.input.items.items))
+        .chain(args.input.scan.clone())
+        .map(|path| {
+            if input::is_stdin(&path) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "cannot watch stdin",
+                ));
+            }
+
+            path.canonicalize()
+        })
+        .collect::<io::Result<_>>()?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+
+    // Watching the directories rather than the files themselves keeps working when an editor
+    // saves by replacing a file.
+    let directories: BTreeSet<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+
+    for directory in directories {
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+    }
+
+    loop {
+        // A half-finished edit shouldn't end the session, so errors are only reported.
+        if let Err(error) = solve(args) {
+            eprintln!("Error: {error}");
+        }
+
+        if !wait_for_change(&receiver, &files) {
+            return Ok(());
+        }
+
+        eprintln!("Change detected, solving again");
+        println!();
+    }
+}
+
+/// Blocks until one of `files` changes, then until no further events arrive for [`DEBOUNCE`].
+/// Returns `false` if the watcher stopped.
+fn wait_for_change(receiver: &Receiver<notify::Result<Event>>, files: &BTreeSet<PathBuf>) -> bool {
+    loop {
+        match receiver.recv() {
+            Ok(Ok(event)) if is_relevant(&event, files) => break,
+            Ok(Ok(_)) => {}
+            Ok(Err(error)) => eprintln!("Warning: failed to watch for changes: {error}"),
+            Err(_) => return false,
+        }
+    }
+
+    while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+
+    true
+}
+
+fn is_relevant(event: &Event, files: &BTreeSet<PathBuf>) -> bool {
+    let is_change = matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    );
+
+    is_change && event.paths.iter().any(|path| files.contains(path))
+}